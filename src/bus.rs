@@ -1,106 +1,163 @@
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cpu::CpuBus;
+use crate::mapper::{self, Mapper, Mirroring, Nrom};
 use crate::util::*;
+use crate::ppu::PALETTE_ADDRESS;
 use crate::Ppu;
-use std::fs::read;
 
-const cpu_memory_size: usize = 0x10000;
-const ppu_memory_size: usize = 0x4000;
+// The console's internal RAM, mirrored four times over $0000-$1FFF
+pub const RAM_SIZE: usize = 0x800;
+// Nametable RAM: the console's 2KB, doubled for four-screen boards which
+// bring their own extra 2KB on the cartridge
+pub const VRAM_SIZE: usize = 0x1000;
+pub const PALETTE_SIZE: usize = 0x20;
+
+// Sources that can hold the CPU /IRQ line low, kept as bits in `irq_sources`
+pub const IRQ_FRAME_COUNTER: u8 = 1 << 0;
+pub const IRQ_DMC: u8 = 1 << 1;
 
 pub struct Bus {
-    pub cpu_memory: [u8; cpu_memory_size + 1],
-    pub ppu_memory: [u8; ppu_memory_size + 1],
-    pub input: u8,
+    pub ram: [u8; RAM_SIZE],
+    // last value written to each PPU register ($2000-$2007), and to each
+    // APU and I/O register ($4000-$4017)
+    pub ppu_registers: [u8; 8],
+    pub io_registers: [u8; 0x18],
+    pub vram: [u8; VRAM_SIZE],
+    pub palette_ram: [u8; PALETTE_SIZE],
+    pub mapper: Box<dyn Mapper>,
+    // buttons held on each controller port, and the shift registers the
+    // cpu reads them back through one bit at a time
+    pub input: [u8; 2],
+    pub shifters: [u8; 2],
     pub polling: bool,
+    pub irq_sources: u8,
+    pub nmi: bool,
+    // last value on the CPU data bus, which is what reads of undriven bits
+    // see as the charge left on the lines
+    pub open_bus: u8,
+    // set by a $4014 write until the cpu picks up the DMA stall
+    pub dma: bool,
 }
 
 impl Bus {
     pub fn new() -> Bus {
         Bus {
-            cpu_memory: [0; cpu_memory_size + 1],
-            ppu_memory: [0; ppu_memory_size + 1],
-            input: 0,
+            ram: [0; RAM_SIZE],
+            ppu_registers: [0; 8],
+            io_registers: [0; 0x18],
+            vram: [0; VRAM_SIZE],
+            palette_ram: [0; PALETTE_SIZE],
+            mapper: Box::new(Nrom::new(vec![0; 0x4000], vec![], Mirroring::Horizontal)),
+            input: [0; 2],
+            shifters: [0; 2],
             polling: false,
+            irq_sources: 0,
+            nmi: false,
+            open_bus: 0,
+            dma: false,
         }
     }
 
-    pub fn load_cartridge(&mut self, path: &str) {
-        let rom = match read(path) {
-            Ok(res) => res,
-            Err(why) => panic!("{}", why),
-        };
+    pub fn load_cartridge(&mut self, path: &str) -> Result<(), CartridgeError> {
+        self.load_rom(&std::fs::read(path)?)
+    }
 
-        let six = rom[6];
-        let seven = rom[7];
-        let mapper = combine_low_high(six & 0b10000000, seven & 0b10000000);
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let cartridge = Cartridge::from_bytes(rom)?;
+        self.mapper = mapper::from_cartridge(cartridge)?;
+        Ok(())
+    }
 
-        match mapper {
-            0 => {
-                for i in 0x10..0x4010 {
-                    self.cpu_memory[0x8000 + i - 0x10] = rom[i];
-                    self.cpu_memory[0xC000 + i - 0x10] = rom[i];
-                }
-                for i in 0x4010..0x6010 {
-                    self.ppu_memory[0 + i - 0x4010] = rom[i];
-                }
-            }
-            _ => panic!("unrecognized mapper"),
+    // Palette RAM lives inside the PPU, so palette accesses never reach the
+    // cartridge's view of the address lines
+    pub fn ppu_observe_addr(&mut self, addr: u16) {
+        if addr < PALETTE_ADDRESS {
+            self.mapper.ppu_address(addr);
         }
     }
 
-    pub fn ppu_check_addr_in_range(&mut self, addr: usize) {
-        if addr > ppu_memory_size {
-            panic!("bus memory address out of range");
+    pub fn set_irq(&mut self, source: u8, active: bool) {
+        if active {
+            self.irq_sources |= source;
+        } else {
+            self.irq_sources &= !source;
         }
     }
 
-    pub fn cpu_check_addr_in_range(&mut self, addr: usize) {
-        if addr > cpu_memory_size {
-            panic!("bus memory address out of range");
-        }
+    // The /IRQ line is level triggered and wired-OR, so any source holds it
+    pub fn irq(&self) -> bool {
+        self.irq_sources != 0 || self.mapper.irq()
     }
 
     pub fn ppu_write_16(&mut self, addr: u16, val: u8) {
-        let u_addr = addr as usize;
-        self.ppu_check_addr_in_range(u_addr);
-        self.ppu_memory[u_addr] = val;
+        // The PPU only has 14 address lines
+        let addr = addr & 0x3FFF;
+        self.ppu_observe_addr(addr);
+        if addr < 0x2000 {
+            self.mapper.ppu_write(addr, val);
+        } else if addr < PALETTE_ADDRESS {
+            self.vram[nametable_index(self.mapper.mirroring(), addr)] = val;
+        } else {
+            self.palette_ram[palette_index(addr)] = val;
+        }
     }
 
+    // The CPU address decoder, without the PPU's side effects:
+    // $0000-$1FFF  2KB RAM, mirrored
+    // $2000-$3FFF  PPU registers, mirrored every 8 bytes
+    // $4000-$4017  APU and I/O registers
+    // $4018-$401F  APU test mode registers, disabled on retail consoles
+    // $4020-$FFFF  cartridge
     pub fn cpu_write_16(&mut self, addr: u16, val: u8) {
-        let u_addr = addr as usize;
-        self.cpu_check_addr_in_range(u_addr);
-        if addr == INPUT {
-            if val == 1 {
-                self.polling = true;
-                self.cpu_memory[INPUT as usize] = self.input;
-            } else if val == 0 {
-                self.polling = false;
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)] = val,
+            0x2000..=0x3FFF => self.ppu_registers[addr as usize & 0x07] = val,
+            0x4000..=0x4017 => {
+                if addr == INPUT {
+                    // Only bit 0 drives the strobe line
+                    self.polling = val & 0x01 != 0;
+                    if self.polling {
+                        self.shifters = self.input;
+                    }
+                }
+                self.io_registers[(addr - 0x4000) as usize] = val;
             }
-            return;
+            0x4018..=0x401F => {}
+            _ => self.mapper.cpu_write(addr, val),
         }
-        self.cpu_memory[u_addr] = val;
     }
 
     pub fn cpu_ppu_reg_addr_map(&mut self, addr: u16) -> u16 {
-        let mut mut_addr = addr;
-        if mut_addr < 0x4000 && mut_addr > 0x2007 {
-            mut_addr = 0x2000 + ((mut_addr - 0x2007) % 8);
+        if (0x2000..0x4000).contains(&addr) {
+            0x2000 + (addr & 0x07)
+        } else {
+            addr
         }
-        mut_addr
     }
 
     pub fn cpu_write_16_ppu_regs(&mut self, addr: u16, val: u8, ppu: &mut Ppu) {
+        self.open_bus = val;
         let mut_addr = self.cpu_ppu_reg_addr_map(addr);
+        if (0x2000..0x2008).contains(&mut_addr) {
+            ppu.io_latch = val;
+        }
         self.cpu_write_16(mut_addr, val);
 
         if mut_addr == DATA {
             ppu.write_data(val, self);
         } else if mut_addr == ADDR {
             ppu.write_addr(val);
-        } else if mut_addr == OAM_DATA { // Only using OAM DMA for now
+        } else if mut_addr == OAM_DATA {
+            ppu.write_oam_data(val);
         } else if mut_addr == OAM_ADDR {
+            ppu.oam_addr = val;
         } else if mut_addr == OAM_DMA {
             ppu.write_oam(val, self);
+            self.dma = true;
+        } else if mut_addr == SCROLL {
+            ppu.write_scroll(val);
         } else if mut_addr == CONTROL {
-            ppu.control.read(self);
+            ppu.write_control(self);
         } else if mut_addr == MASK {
             ppu.mask.read(self);
         }
@@ -118,86 +175,125 @@ impl Bus {
 
     // Read absolute address
     pub fn ppu_read_16(&mut self, addr: u16) -> u8 {
-        let u_addr = addr as usize;
-        self.ppu_check_addr_in_range(u_addr);
-        self.ppu_memory[u_addr]
+        let addr = addr & 0x3FFF;
+        self.ppu_observe_addr(addr);
+        if addr < 0x2000 {
+            self.mapper.ppu_read(addr)
+        } else if addr < PALETTE_ADDRESS {
+            self.vram[nametable_index(self.mapper.mirroring(), addr)]
+        } else {
+            self.palette_ram[palette_index(addr)]
+        }
     }
 
     pub fn cpu_read_16_ppu_regs(&mut self, addr: u16, ppu: &mut Ppu) -> u8 {
-        let u_addr = addr as usize;
-        let mut_addr = self.cpu_ppu_reg_addr_map(addr) as usize;
-        let mut temp = self.cpu_read_16(mut_addr as u16);
-
-        if mut_addr == (STATUS as usize) {
-            self.cpu_memory[STATUS as usize] = set_u8_bit(self.cpu_memory[STATUS as usize], 7, 0);
-            if ppu.line == 240 && ppu.cycle >= 2 && ppu.cycle < 5 {
-                temp = self.cpu_memory[mut_addr];
-            }
+        let mut_addr = self.cpu_ppu_reg_addr_map(addr);
+        let val = if mut_addr == STATUS {
+            ppu.read_status(self)
+        } else if mut_addr == DATA {
+            ppu.read_data(self)
+        } else if mut_addr == OAM_DATA {
+            ppu.read_oam_data()
+        } else if (CONTROL..=DATA).contains(&mut_addr) {
+            // the write-only registers
+            ppu.io_latch
+        } else {
+            self.cpu_read_16(mut_addr)
+        };
+        // $4015 is inside the CPU, its reads never reach the external bus
+        if mut_addr != APU_STATUS {
+            self.open_bus = val;
         }
+        val
+    }
 
-        temp
+    // Read without side effects (controller shifts etc.), for debuggers and logs
+    pub fn cpu_peek(&mut self, addr: u16) -> u8 {
+        match addr {
+            INPUT | INPUT_2 => self.shifters[(addr - INPUT) as usize] & 0x01,
+            _ => self.cpu_read_16(addr),
+        }
     }
 
     pub fn cpu_read_16(&mut self, addr: u16) -> u8 {
-        let u_addr = addr as usize;
-        self.cpu_check_addr_in_range(u_addr);
-        let mut ret = self.cpu_memory[u_addr].clone();
-        if addr == INPUT {
-			ret &= 0x01;
-			if !self.polling {
-				self.cpu_memory[u_addr] >>= 1;
-			}
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)],
+            0x2000..=0x3FFF => self.ppu_registers[addr as usize & 0x07],
+            INPUT | INPUT_2 => {
+                let port = (addr - INPUT) as usize;
+                if self.polling {
+                    self.shifters[port] = self.input[port];
+                }
+                let ret = self.shifters[port] & 0x01;
+                if !self.polling {
+                    // Official controllers read back 1 once all 8 buttons are out
+                    self.shifters[port] = self.shifters[port] >> 1 | 0x80;
+                }
+                // The ports only drive the low bits, so e.g. LDA $4016
+                // reads $40 or $41
+                ret | self.open_bus & 0xE0
+            }
+            // APU status, with nothing playing; bit 5 isn't driven
+            APU_STATUS => self.open_bus & 0x20,
+            // the rest of the APU and I/O registers are write only, and test
+            // mode is disabled
+            0x4000..=0x401F => self.open_bus,
+            _ => self.mapper.cpu_read(addr).unwrap_or(self.open_bus),
         }
-        ret
     }
+}
 
-    // Read one byte in relation to the PC
-    pub fn read_single(&mut self, addr: u16) -> u8 {
-        self.cpu_read_16(addr - 1)
-    }
+// Folds a nametable address ($2000-$3EFF, $3000 up mirroring $2000) onto
+// VRAM. The four logical tables sit in a 2x2 grid and the board's mirroring
+// decides which 1KB page backs each of them
+pub fn nametable_index(mirroring: Mirroring, addr: u16) -> usize {
+    let addr = (addr & 0x0FFF) as usize;
+    let table = addr / 0x400;
+    let page = match mirroring {
+        Mirroring::Horizontal => table / 2,
+        Mirroring::Vertical => table % 2,
+        Mirroring::SingleScreenLower => 0,
+        Mirroring::SingleScreenUpper => 1,
+        Mirroring::FourScreen => table,
+    };
+    page * 0x400 + (addr & 0x3FF)
+}
 
-    // Read two bytes in relation to the PC
-    pub fn read_double(&mut self, addr: u16) -> u16 {
-        combine_low_high(self.cpu_read_16(addr - 2), self.cpu_read_16(addr - 1))
+// Palette RAM is 32 bytes mirrored up to $3FFF, and the sprite palettes'
+// entry 0 ($3F10/$3F14/$3F18/$3F1C) is the same byte as the background's
+pub fn palette_index(addr: u16) -> usize {
+    let index = (addr & 0x1F) as usize;
+    if index & 0x13 == 0x10 {
+        index & 0x0F
+    } else {
+        index
     }
+}
+
+// What the CPU sees on its side of the bus: memory plus the PPU registers
+pub struct SystemBus<'a> {
+    pub bus: &'a mut Bus,
+    pub ppu: &'a mut Ppu,
+}
 
-    // Does addressing mode Rel cross the page?
-    pub fn cross_rel(&mut self, pc: u16) -> u8 {
-        let offset = self.cpu_read_16(pc + 1) as i8 as i16;
-        let new = (pc + 2).wrapping_add_signed(offset);
-        (get_u16_bit(pc + 2, 8) != get_u16_bit(new, 8)) as u8
+impl<'a> CpuBus for SystemBus<'a> {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.bus.cpu_read_16_ppu_regs(addr, self.ppu)
     }
 
-    // Does addressing mode Indexed Y cross the page?
-    pub fn cross_idy(&mut self, pc: u16, offset: u8) -> u8 {
-        let addr = self.cpu_read_16(pc + 1);
-        let low = self.cpu_read_8(addr);
-        let (high_addr, overflow) = addr.overflowing_add(1);
-        if overflow {
-            return 1;
-        }
-        let high = self.cpu_read_8(high_addr);
-        (combine_low_high(low, high) as u8)
-            .overflowing_add(offset as u8)
-            .1 as u8
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        self.bus.cpu_write_16_ppu_regs(addr, val, self.ppu)
     }
 
-    // Does addressing mode Indexed X cross the page?
-    pub fn cross_abs(&mut self, pc: u16, offset: u8) -> u8 {
-        let low = self.cpu_read_16(pc + 1);
-        let high = self.cpu_read_16(pc + 2);
-        low.overflowing_add(offset).1 as u8
+    fn nmi(&self) -> bool {
+        self.bus.nmi
     }
 
-    pub fn DEC(&mut self, addr: u16) -> u8 {
-        let val = self.cpu_read_16(addr).wrapping_sub(1);
-        self.cpu_write_16(addr, val);
-        val
+    fn irq(&self) -> bool {
+        self.bus.irq()
     }
 
-    pub fn INC(&mut self, addr: u16) -> u8 {
-        let val = self.cpu_read_16(addr).wrapping_add(1);
-        self.cpu_write_16(addr, val);
-        val
+    fn take_dma(&mut self) -> bool {
+        std::mem::take(&mut self.bus.dma)
     }
 }
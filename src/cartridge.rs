@@ -0,0 +1,274 @@
+use crate::mapper::Mirroring;
+use crate::util::*;
+
+use std::fmt;
+
+const HEADER_SIZE: usize = 0x10;
+const TRAINER_SIZE: usize = 0x200;
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+const MAGIC: [u8; 4] = [b'N', b'E', b'S', 0x1A];
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum HeaderFormat {
+    INes,
+    Nes2,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    MultiRegion,
+    Dendy,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ConsoleType {
+    Nes,
+    VsSystem,
+    Playchoice10,
+    Extended(u8),
+}
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    Io(std::io::Error),
+    TooShort(usize),
+    BadMagic([u8; 4]),
+    Truncated { expected: usize, actual: usize },
+    SizeOverflow,
+    NoPrgRom,
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Io(why) => write!(f, "could not read rom: {}", why),
+            CartridgeError::TooShort(len) => {
+                write!(f, "rom is {} bytes, too short for a 16 byte header", len)
+            }
+            CartridgeError::BadMagic(magic) => {
+                write!(f, "bad header magic {:02x?}, expected \"NES\\x1A\"", magic)
+            }
+            CartridgeError::Truncated { expected, actual } => write!(
+                f,
+                "rom is truncated: header describes {} bytes but file has {}",
+                expected, actual
+            ),
+            CartridgeError::SizeOverflow => {
+                write!(f, "header describes a rom too large to address")
+            }
+            CartridgeError::NoPrgRom => write!(f, "header describes no PRG ROM"),
+            CartridgeError::UnsupportedMapper(mapper) => {
+                write!(f, "unsupported mapper {}", mapper)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+impl From<std::io::Error> for CartridgeError {
+    fn from(why: std::io::Error) -> CartridgeError {
+        CartridgeError::Io(why)
+    }
+}
+
+pub struct Cartridge {
+    pub format: HeaderFormat,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub trainer: Option<Vec<u8>>,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub battery: bool,
+    pub mirroring: Mirroring,
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+}
+
+// NES 2.0 RAM sizes are stored as a shift count: 0 means none, otherwise 64 << n
+fn parse_ram_shift(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+// NES 2.0 ROM sizes are a 12 bit bank count, unless the MSB nibble is $F in
+// which case the LSB holds an exponent-multiplier pair: 2^E * (MM * 2 + 1).
+// None if that doesn't fit in a usize
+fn parse_rom_size(lsb: u8, msb: u8, bank_size: usize) -> Option<usize> {
+    if msb == 0x0F {
+        let exponent = get_u8_bits(lsb, 7, 2) as u32;
+        let multiplier = get_u8_bits(lsb, 1, 0) as usize * 2 + 1;
+        1usize.checked_shl(exponent)?.checked_mul(multiplier)
+    } else {
+        (combine_low_high(lsb, msb) as usize).checked_mul(bank_size)
+    }
+}
+
+impl Cartridge {
+    pub fn from_bytes(rom: &[u8]) -> Result<Cartridge, CartridgeError> {
+        if rom.len() < HEADER_SIZE {
+            return Err(CartridgeError::TooShort(rom.len()));
+        }
+        let magic = [rom[0], rom[1], rom[2], rom[3]];
+        if magic != MAGIC {
+            return Err(CartridgeError::BadMagic(magic));
+        }
+
+        let six = rom[6];
+        let seven = rom[7];
+        let format = if seven & 0x0C == 0x08 {
+            HeaderFormat::Nes2
+        } else {
+            HeaderFormat::INes
+        };
+
+        let mirroring = if get_u8_bit(six, 3) == 1 {
+            Mirroring::FourScreen
+        } else if get_u8_bit(six, 0) == 1 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let battery = get_u8_bit(six, 1) == 1;
+        let has_trainer = get_u8_bit(six, 2) == 1;
+        let mapper_low = (six >> 4) as u16;
+
+        let mut cartridge = match format {
+            HeaderFormat::Nes2 => Cartridge {
+                format,
+                mapper: mapper_low | (seven & 0xF0) as u16 | ((rom[8] & 0x0F) as u16) << 8,
+                submapper: rom[8] >> 4,
+                prg_rom: vec![],
+                chr_rom: vec![],
+                trainer: None,
+                prg_ram_size: parse_ram_shift(rom[10] & 0x0F),
+                prg_nvram_size: parse_ram_shift(rom[10] >> 4),
+                chr_ram_size: parse_ram_shift(rom[11] & 0x0F),
+                chr_nvram_size: parse_ram_shift(rom[11] >> 4),
+                battery,
+                mirroring,
+                timing: match rom[12] & 0x03 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                },
+                console_type: match seven & 0x03 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem,
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(rom[13] & 0x0F),
+                },
+            },
+            HeaderFormat::INes => {
+                // Old dumps ("DiskDude!") put garbage in bytes 12-15; the upper
+                // mapper nibble can't be trusted in that case
+                let dirty = rom[12..16].iter().any(|b| *b != 0);
+                let mapper_high = if dirty { 0 } else { (seven & 0xF0) as u16 };
+                let prg_ram_size = if rom[8] == 0 {
+                    0x2000
+                } else {
+                    rom[8] as usize * 0x2000
+                };
+                Cartridge {
+                    format,
+                    mapper: mapper_low | mapper_high,
+                    submapper: 0,
+                    prg_rom: vec![],
+                    chr_rom: vec![],
+                    trainer: None,
+                    prg_ram_size: if battery { 0 } else { prg_ram_size },
+                    prg_nvram_size: if battery { prg_ram_size } else { 0 },
+                    chr_ram_size: if rom[5] == 0 { CHR_BANK_SIZE } else { 0 },
+                    chr_nvram_size: 0,
+                    battery,
+                    mirroring,
+                    timing: if get_u8_bit(rom[9], 0) == 1 {
+                        Timing::Pal
+                    } else {
+                        Timing::Ntsc
+                    },
+                    console_type: if get_u8_bit(seven, 0) == 1 {
+                        ConsoleType::VsSystem
+                    } else if get_u8_bit(seven, 1) == 1 {
+                        ConsoleType::Playchoice10
+                    } else {
+                        ConsoleType::Nes
+                    },
+                }
+            }
+        };
+
+        let (prg_size, chr_size) = match format {
+            HeaderFormat::Nes2 => (
+                parse_rom_size(rom[4], rom[9] & 0x0F, PRG_BANK_SIZE)
+                    .ok_or(CartridgeError::SizeOverflow)?,
+                parse_rom_size(rom[5], rom[9] >> 4, CHR_BANK_SIZE)
+                    .ok_or(CartridgeError::SizeOverflow)?,
+            ),
+            HeaderFormat::INes => (
+                rom[4] as usize * PRG_BANK_SIZE,
+                rom[5] as usize * CHR_BANK_SIZE,
+            ),
+        };
+
+        if prg_size == 0 {
+            return Err(CartridgeError::NoPrgRom);
+        }
+
+        let trainer_size = if has_trainer { TRAINER_SIZE } else { 0 };
+        let prg_start = HEADER_SIZE + trainer_size;
+        let chr_start = prg_start
+            .checked_add(prg_size)
+            .ok_or(CartridgeError::SizeOverflow)?;
+        let chr_end = chr_start
+            .checked_add(chr_size)
+            .ok_or(CartridgeError::SizeOverflow)?;
+        if rom.len() < chr_end {
+            return Err(CartridgeError::Truncated {
+                expected: chr_end,
+                actual: rom.len(),
+            });
+        }
+
+        if has_trainer {
+            cartridge.trainer = Some(rom[HEADER_SIZE..prg_start].to_vec());
+        }
+        cartridge.prg_rom = rom[prg_start..chr_start].to_vec();
+        cartridge.chr_rom = rom[chr_start..chr_end].to_vec();
+        Ok(cartridge)
+    }
+
+    // CHR ROM, or zeroed CHR RAM (at least 8K) for boards without CHR ROM
+    pub fn chr_memory(&self) -> Vec<u8> {
+        if self.has_chr_ram() {
+            vec![0; (self.chr_ram_size + self.chr_nvram_size).max(CHR_BANK_SIZE)]
+        } else {
+            self.chr_rom.clone()
+        }
+    }
+
+    pub fn has_chr_ram(&self) -> bool {
+        self.chr_rom.is_empty()
+    }
+
+    // Zeroed PRG RAM (at least 8K) for the $6000-$7FFF window
+    pub fn prg_ram(&self) -> Vec<u8> {
+        vec![0; (self.prg_ram_size + self.prg_nvram_size).max(0x2000)]
+    }
+
+    pub fn from_path(path: &str) -> Result<Cartridge, CartridgeError> {
+        Cartridge::from_bytes(&std::fs::read(path)?)
+    }
+}
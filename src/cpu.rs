@@ -1,31 +1,65 @@
+use crate::opcodes::OPCODES;
 use crate::util::*;
 use crate::Bus;
-use crate::Ppu;
-
-use std::time::{Duration, Instant};
-
-use csv::StringRecord;
-
-const ERR_OP: &str = "Invalid Opcode";
-const ERR_ADDR: &str = "Invalid Addressing Mode";
 
 #[rustfmt::skip]
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 #[derive(Debug)]
 pub enum Instructions {
     ADC, AND, ASL, BCC, BCS, BEQ, BIT, BMI, BNE, BPL, BRK, BVC, BVS, CLC,
     CLD, CLI, CLV, CMP, CPX, CPY, DEC, DEX, DEY, EOR, INC, INX, INY, JMP,
     JSR, LDA, LDX, LDY, LSR, NOP, ORA, PHA, PHP, PLA, PLP, ROL, ROR, RTI,
     RTS, SBC, SEC, SED, SEI, STA, STX, STY, TAX, TAY, TSX, TXA, TXS, TYA,
+    // unofficial
+    ALR, ANC, ARR, AXS, DCP, ISC, KIL, LAS, LAX, LXA, RLA, RRA, SAX, SHA,
+    SHX, SHY, SLO, SRE, TAS, XAA,
 }
 
 #[rustfmt::skip]
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 #[derive(Debug)]
 pub enum Addressing {
     IMP, ACC, IMM, ZPG, ZPX, ZPY, REL, ABS, ABX, ABY, IND, IDX, IDY,
 }
 
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Interrupt {
+    NMI,
+    IRQ,
+    RESET,
+}
+
+// How an instruction uses the memory operand its addressing mode produces
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Access {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+// Everything the CPU can see of the rest of the system. Each read or write
+// is exactly one CPU cycle.
+pub trait CpuBus {
+    fn cpu_read(&mut self, addr: u16) -> u8;
+    fn cpu_write(&mut self, addr: u16, val: u8);
+
+    // Level of the /NMI line (true = asserted)
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // Level of the /IRQ line (true = asserted)
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Whether the last write started an OAM DMA, which halts the cpu while
+    // it copies a page to the PPU. Clears the request
+    fn take_dma(&mut self) -> bool {
+        false
+    }
+}
+
 pub struct Cpu {
     // registers
     pub a: u8,
@@ -45,6 +79,24 @@ pub struct Cpu {
     pub addr: Addressing,
     pub stack: [u8; 256],
     pub stack_pointer: u8,
+    // current instruction; cycle 0 is the opcode fetch
+    pub opcode: u8,
+    pub cycle: u8,
+    pub cycles: u64,
+    target_addr: u16,
+    pointer: u8,
+    data: u8,
+    page_crossed: bool,
+    // interrupts
+    pub interrupt: Option<Interrupt>,
+    pub nmi_pending: bool,
+    pub nmi_previous: bool,
+    run_interrupt: bool,
+    prev_run_interrupt: bool,
+    // set by KIL, only a reset gets the cpu going again
+    pub jammed: bool,
+    // cycles left halted for OAM DMA
+    pub stall: u16,
 }
 
 impl Cpu {
@@ -67,47 +119,693 @@ impl Cpu {
             instr: Instructions::NOP,
             addr: Addressing::IMM,
             stack: [0; 256],
-            stack_pointer: 0xFD,
+            // the reset sequence takes this down to $FD
+            stack_pointer: 0,
+            // current instruction
+            opcode: 0,
+            cycle: 0,
+            cycles: 0,
+            target_addr: 0,
+            pointer: 0,
+            data: 0,
+            page_crossed: false,
+            // interrupts
+            interrupt: None,
+            nmi_pending: false,
+            nmi_previous: false,
+            run_interrupt: false,
+            prev_run_interrupt: false,
+            jammed: false,
+            stall: 0,
         }
     }
 
-    // --------------- INSTRUCTIONS --------------------
+    // --------------- CONTROL --------------------
+
+    // The reset sequence runs over the next 7 calls to step
+    pub fn Reset(&mut self) {
+        self.jammed = false;
+        self.cycle = 0;
+        self.interrupt = Some(Interrupt::RESET);
+    }
+
+    // Run one CPU cycle, doing exactly one bus access
+    pub fn step<B: CpuBus>(&mut self, bus: &mut B) {
+        if self.stall > 0 {
+            self.stall -= 1;
+        } else if self.jammed {
+            bus.cpu_read(0xFFFF);
+        } else if self.cycle == 0 {
+            self.fetch(bus);
+        } else {
+            let done = match self.instr {
+                Instructions::BRK => self.cycle_interrupt(bus),
+                Instructions::RTI => self.cycle_rti(bus),
+                Instructions::RTS => self.cycle_rts(bus),
+                Instructions::JSR => self.cycle_jsr(bus),
+                Instructions::JMP => self.cycle_jmp(bus),
+                Instructions::PHA | Instructions::PHP => self.cycle_push(bus),
+                Instructions::PLA | Instructions::PLP => self.cycle_pull(bus),
+                Instructions::KIL => {
+                    bus.cpu_read(self.pc);
+                    // The cpu locks up re-reading the same opcode
+                    self.pc = self.pc.wrapping_sub(1);
+                    self.jammed = true;
+                    true
+                }
+                _ => match self.addr {
+                    Addressing::IMP | Addressing::ACC => {
+                        bus.cpu_read(self.pc);
+                        self.implied_op();
+                        true
+                    }
+                    Addressing::IMM => {
+                        let val = self.fetch_operand(bus);
+                        self.read_op(val);
+                        true
+                    }
+                    Addressing::REL => self.cycle_branch(bus),
+                    _ => self.cycle_memory(bus),
+                },
+            };
+            self.cycle = if done { 0 } else { self.cycle + 1 };
+        }
+        self.poll_interrupts(bus);
+        self.cycles += 1;
+        // OAM DMA takes 513 cycles, plus one to line up with a read cycle
+        if bus.take_dma() {
+            self.stall = 513 + (self.cycles & 1) as u16;
+        }
+    }
+
+    // Run cycles until the current instruction (or interrupt sequence) is done
+    pub fn step_instruction<B: CpuBus>(&mut self, bus: &mut B) -> u16 {
+        let mut cycles = 0;
+        loop {
+            self.step(bus);
+            cycles += 1;
+            if (self.cycle == 0 && self.stall == 0) || self.jammed {
+                return cycles;
+            }
+        }
+    }
+
+    // Sample the interrupt lines at the end of a cycle. NMI is edge triggered
+    // and latched; IRQ is a level. An interrupt is taken at the next opcode
+    // fetch if it was seen at the end of the instruction's second-to-last cycle.
+    fn poll_interrupts<B: CpuBus>(&mut self, bus: &mut B) {
+        let nmi = bus.nmi();
+        if nmi && !self.nmi_previous {
+            self.nmi_pending = true;
+        }
+        self.nmi_previous = nmi;
+        self.prev_run_interrupt = self.run_interrupt;
+        self.run_interrupt = self.nmi_pending || (bus.irq() && !self.i);
+    }
+
+    fn fetch<B: CpuBus>(&mut self, bus: &mut B) {
+        if self.interrupt.is_none() && self.prev_run_interrupt {
+            self.interrupt = Some(if self.nmi_pending {
+                Interrupt::NMI
+            } else {
+                Interrupt::IRQ
+            });
+        }
+        if self.interrupt.is_some() {
+            // The fetched opcode is thrown away and BRK's sequence runs instead
+            bus.cpu_read(self.pc);
+            self.instr = Instructions::BRK;
+            self.addr = Addressing::IMP;
+        } else {
+            self.opcode = bus.cpu_read(self.pc);
+            self.pc = self.pc.wrapping_add(1);
+            let opcode = &OPCODES[self.opcode as usize];
+            self.instr = opcode.instr;
+            self.addr = opcode.addr;
+        }
+        self.cycle = 1;
+    }
+
+    fn fetch_operand<B: CpuBus>(&mut self, bus: &mut B) -> u8 {
+        let val = bus.cpu_read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        val
+    }
+
+    fn access(&self) -> Access {
+        match self.instr {
+            Instructions::STA
+            | Instructions::STX
+            | Instructions::STY
+            | Instructions::SAX
+            | Instructions::SHA
+            | Instructions::SHX
+            | Instructions::SHY
+            | Instructions::TAS => Access::Write,
+            Instructions::ASL
+            | Instructions::LSR
+            | Instructions::ROL
+            | Instructions::ROR
+            | Instructions::INC
+            | Instructions::DEC
+            | Instructions::SLO
+            | Instructions::RLA
+            | Instructions::SRE
+            | Instructions::RRA
+            | Instructions::DCP
+            | Instructions::ISC => Access::ReadModifyWrite,
+            _ => Access::Read,
+        }
+    }
+
+    // --------------- CYCLES --------------------
+
+    // BRK, IRQ, NMI and RESET share one sequence. B is only pushed set for
+    // BRK, RESET turns the pushes into reads, and an NMI that shows up
+    // before the vector is picked hijacks BRK/IRQ onto the NMI vector.
+    fn cycle_interrupt<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        let reset = self.interrupt == Some(Interrupt::RESET);
+        match self.cycle {
+            1 => {
+                bus.cpu_read(self.pc);
+                // skip BRK's padding byte
+                if self.interrupt.is_none() {
+                    self.pc = self.pc.wrapping_add(1);
+                }
+            }
+            2..=4 => {
+                let val = match self.cycle {
+                    2 => (self.pc >> 8) as u8,
+                    3 => self.pc as u8,
+                    _ => set_u8_bit(self.flags_to_byte(), 4, self.interrupt.is_none() as u8),
+                };
+                if reset {
+                    bus.cpu_read(self.stack_pointer_to_addr());
+                    self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+                } else {
+                    self.stack_push(val, bus);
+                }
+                if self.cycle == 4 {
+                    self.target_addr = if reset {
+                        0xFFFC
+                    } else if self.nmi_pending {
+                        self.nmi_pending = false;
+                        0xFFFA
+                    } else {
+                        0xFFFE
+                    };
+                }
+            }
+            5 => {
+                self.data = bus.cpu_read(self.target_addr);
+                self.flag_interrupt(true);
+            }
+            _ => {
+                self.pc = combine_low_high(self.data, bus.cpu_read(self.target_addr + 1));
+                self.interrupt = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    fn cycle_rti<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => {
+                bus.cpu_read(self.pc);
+            }
+            2 => {
+                bus.cpu_read(self.stack_pointer_to_addr());
+            }
+            3 => self.PLP(bus),
+            4 => self.data = self.stack_pull(bus),
+            _ => {
+                self.pc = combine_low_high(self.data, self.stack_pull(bus));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn cycle_rts<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => {
+                bus.cpu_read(self.pc);
+            }
+            2 => {
+                bus.cpu_read(self.stack_pointer_to_addr());
+            }
+            3 => self.data = self.stack_pull(bus),
+            4 => self.pc = combine_low_high(self.data, self.stack_pull(bus)),
+            _ => {
+                bus.cpu_read(self.pc);
+                self.pc = self.pc.wrapping_add(1);
+                return true;
+            }
+        }
+        false
+    }
+
+    // JSR pushes the address of its own last byte, RTS adds the 1 back
+    fn cycle_jsr<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => self.data = self.fetch_operand(bus),
+            2 => {
+                bus.cpu_read(self.stack_pointer_to_addr());
+            }
+            3 => self.stack_push((self.pc >> 8) as u8, bus),
+            4 => self.stack_push(self.pc as u8, bus),
+            _ => {
+                self.pc = combine_low_high(self.data, bus.cpu_read(self.pc));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn cycle_jmp<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match (self.addr, self.cycle) {
+            (_, 1) => self.data = self.fetch_operand(bus),
+            (Addressing::ABS, _) => {
+                self.pc = combine_low_high(self.data, bus.cpu_read(self.pc));
+                return true;
+            }
+            (_, 2) => self.target_addr = combine_low_high(self.data, self.fetch_operand(bus)),
+            (_, 3) => self.data = bus.cpu_read(self.target_addr),
+            _ => {
+                // The pointer's high byte is read without carrying into the page
+                let high_addr =
+                    (self.target_addr & 0xFF00) | (self.target_addr as u8).wrapping_add(1) as u16;
+                self.pc = combine_low_high(self.data, bus.cpu_read(high_addr));
+                return true;
+            }
+        }
+        false
+    }
+
+    fn cycle_push<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => {
+                bus.cpu_read(self.pc);
+                false
+            }
+            _ => {
+                if self.instr == Instructions::PHA {
+                    self.stack_push(self.a, bus);
+                } else {
+                    self.PHP(bus);
+                }
+                true
+            }
+        }
+    }
+
+    fn cycle_pull<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => {
+                bus.cpu_read(self.pc);
+                false
+            }
+            2 => {
+                bus.cpu_read(self.stack_pointer_to_addr());
+                false
+            }
+            _ => {
+                if self.instr == Instructions::PLA {
+                    self.a = self.stack_pull(bus);
+                    self.flags_from_val(self.a);
+                } else {
+                    self.PLP(bus);
+                }
+                true
+            }
+        }
+    }
+
+    fn cycle_branch<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        match self.cycle {
+            1 => {
+                self.data = self.fetch_operand(bus);
+                !self.branch_taken()
+            }
+            2 => {
+                // A taken branch that stays on the page doesn't poll interrupts on its
+                // last cycle, so one that only just arrived waits an extra instruction
+                if self.run_interrupt && !self.prev_run_interrupt {
+                    self.run_interrupt = false;
+                }
+                bus.cpu_read(self.pc);
+                let new_pc = self.pc.wrapping_add_signed(self.data as i8 as i16);
+                if new_pc & 0xFF00 == self.pc & 0xFF00 {
+                    self.pc = new_pc;
+                    return true;
+                }
+                self.target_addr = new_pc;
+                self.pc = (self.pc & 0xFF00) | (new_pc & 0x00FF);
+                false
+            }
+            _ => {
+                bus.cpu_read(self.pc);
+                self.pc = self.target_addr;
+                true
+            }
+        }
+    }
+
+    // Zero page, absolute and indirect modes: work out the address over a few
+    // cycles (with the dummy reads real hardware does), then read, write or
+    // read-dummy write-write it
+    fn cycle_memory<B: CpuBus>(&mut self, bus: &mut B) -> bool {
+        let access_start = match self.addr {
+            Addressing::ZPG => 2,
+            Addressing::ZPX | Addressing::ZPY | Addressing::ABS => 3,
+            Addressing::ABX | Addressing::ABY => 4,
+            _ => 5,
+        };
+        if self.cycle < access_start {
+            match (self.addr, self.cycle) {
+                (Addressing::IDX, 1) | (Addressing::IDY, 1) => {
+                    self.pointer = self.fetch_operand(bus)
+                }
+                (_, 1) => self.target_addr = self.fetch_operand(bus) as u16,
+                (Addressing::ZPX, _) | (Addressing::ZPY, _) => {
+                    bus.cpu_read(self.target_addr);
+                    let index = if self.addr == Addressing::ZPX {
+                        self.x
+                    } else {
+                        self.y
+                    };
+                    self.target_addr = (self.target_addr as u8).wrapping_add(index) as u16;
+                }
+                (Addressing::ABS, _) => self.target_addr |= (self.fetch_operand(bus) as u16) << 8,
+                (Addressing::ABX, 2) | (Addressing::ABY, 2) => {
+                    let high = self.fetch_operand(bus);
+                    let index = if self.addr == Addressing::ABX {
+                        self.x
+                    } else {
+                        self.y
+                    };
+                    self.index_target(self.target_addr as u8, high, index);
+                }
+                (Addressing::IDX, 2) => {
+                    bus.cpu_read(self.pointer as u16);
+                    self.pointer = self.pointer.wrapping_add(self.x);
+                }
+                (Addressing::IDX, 3) => self.target_addr = bus.cpu_read(self.pointer as u16) as u16,
+                (Addressing::IDX, _) => {
+                    self.target_addr |=
+                        (bus.cpu_read(self.pointer.wrapping_add(1) as u16) as u16) << 8
+                }
+                (Addressing::IDY, 2) => self.data = bus.cpu_read(self.pointer as u16),
+                (Addressing::IDY, 3) => {
+                    let high = bus.cpu_read(self.pointer.wrapping_add(1) as u16);
+                    self.index_target(self.data, high, self.y);
+                }
+                _ => {
+                    // Indexed modes read before the high byte is fixed up; reads that
+                    // didn't cross a page are done, everything else reads again
+                    let val = bus.cpu_read(self.target_addr);
+                    if self.page_crossed {
+                        self.target_addr = self.target_addr.wrapping_add(0x100);
+                    } else if self.access() == Access::Read {
+                        self.read_op(val);
+                        return true;
+                    }
+                }
+            }
+            return false;
+        }
+
+        match (self.access(), self.cycle - access_start) {
+            (Access::Read, _) => {
+                let val = bus.cpu_read(self.target_addr);
+                self.read_op(val);
+                true
+            }
+            (Access::Write, _) => {
+                self.write_op(bus);
+                true
+            }
+            (_, 0) => {
+                self.data = bus.cpu_read(self.target_addr);
+                false
+            }
+            (_, 1) => {
+                bus.cpu_write(self.target_addr, self.data);
+                self.data = self.rmw_op(self.data);
+                false
+            }
+            _ => {
+                bus.cpu_write(self.target_addr, self.data);
+                true
+            }
+        }
+    }
+
+    // Add an index to the low byte only; the carry into the high byte costs
+    // an extra cycle later
+    fn index_target(&mut self, low: u8, high: u8, index: u8) {
+        let (low, crossed) = low.overflowing_add(index);
+        self.page_crossed = crossed;
+        self.target_addr = combine_low_high(low, high);
+    }
+
+    // --------------- OPERATIONS --------------------
 
-    pub fn Reset(&mut self, bus: &mut Bus) {
-        self.JMP(bus, 0xFFFC);
+    fn implied_op(&mut self) {
+        match self.instr {
+            Instructions::ASL | Instructions::LSR | Instructions::ROL | Instructions::ROR => {
+                self.a = self.rmw_op(self.a)
+            }
+            Instructions::CLC => self.flag_carry(false),
+            Instructions::CLD => self.flag_decimal(false),
+            Instructions::CLI => self.flag_interrupt(false),
+            Instructions::CLV => self.flag_overflow(false),
+            Instructions::SEC => self.flag_carry(true),
+            Instructions::SED => self.flag_decimal(true),
+            Instructions::SEI => self.flag_interrupt(true),
+            Instructions::DEX => {
+                let res = self.DEX();
+                self.flags_from_val(res);
+            }
+            Instructions::DEY => {
+                let res = self.DEY();
+                self.flags_from_val(res);
+            }
+            Instructions::INX => {
+                let res = self.INX();
+                self.flags_from_val(res);
+            }
+            Instructions::INY => {
+                let res = self.INY();
+                self.flags_from_val(res);
+            }
+            Instructions::TAX => {
+                self.x = self.a;
+                self.flags_from_val(self.x);
+            }
+            Instructions::TAY => {
+                self.y = self.a;
+                self.flags_from_val(self.y);
+            }
+            Instructions::TSX => {
+                self.x = self.stack_pointer;
+                self.flags_from_val(self.x);
+            }
+            Instructions::TXA => {
+                self.a = self.x;
+                self.flags_from_val(self.a);
+            }
+            Instructions::TYA => {
+                self.a = self.y;
+                self.flags_from_val(self.a);
+            }
+            Instructions::TXS => self.stack_pointer = self.x,
+            _ => (),
+        }
+    }
+
+    fn read_op(&mut self, val: u8) {
+        match self.instr {
+            Instructions::ADC => self.ADC(val, self.c as u8),
+            Instructions::SBC => self.SBC(val),
+            Instructions::AND => {
+                self.a &= val;
+                self.flags_from_val(self.a);
+            }
+            Instructions::ORA => {
+                self.a |= val;
+                self.flags_from_val(self.a);
+            }
+            Instructions::EOR => {
+                self.a ^= val;
+                self.flags_from_val(self.a);
+            }
+            Instructions::BIT => {
+                self.flag_zero_from_val(val & self.a);
+                self.flag_negative_from_val(val);
+                self.flag_overflow(get_u8_bit(val, 6) == 1);
+            }
+            Instructions::CMP => self.compare(self.a, val),
+            Instructions::CPX => self.compare(self.x, val),
+            Instructions::CPY => self.compare(self.y, val),
+            Instructions::LDA => {
+                self.a = val;
+                self.flags_from_val(val);
+            }
+            Instructions::LDX => {
+                self.x = val;
+                self.flags_from_val(val);
+            }
+            Instructions::LDY => {
+                self.y = val;
+                self.flags_from_val(val);
+            }
+            // --------------- UNOFFICIAL --------------------
+            Instructions::LAX => {
+                self.a = val;
+                self.x = val;
+                self.flags_from_val(val);
+            }
+            Instructions::LAS => {
+                let val = val & self.stack_pointer;
+                self.a = val;
+                self.x = val;
+                self.stack_pointer = val;
+                self.flags_from_val(val);
+            }
+            Instructions::ANC => {
+                self.a &= val;
+                self.flags_from_val(self.a);
+                self.flag_carry(self.n);
+            }
+            Instructions::ALR => self.a = self.LSR(self.a & val),
+            Instructions::ARR => {
+                self.a = self.ROR(self.a & val);
+                self.flag_carry(get_u8_bit(self.a, 6) == 1);
+                self.flag_overflow(get_u8_bit(self.a, 6) ^ get_u8_bit(self.a, 5) == 1);
+            }
+            Instructions::AXS => {
+                let and = self.a & self.x;
+                self.x = and.wrapping_sub(val);
+                self.flag_carry(and >= val);
+                self.flags_from_val(self.x);
+            }
+            // XAA and LXA are unstable on real hardware; this is the commonly
+            // accepted approximation with the magic constant $EE
+            Instructions::XAA => {
+                self.a = (self.a | 0xEE) & self.x & val;
+                self.flags_from_val(self.a);
+            }
+            Instructions::LXA => {
+                self.a = (self.a | 0xEE) & val;
+                self.x = self.a;
+                self.flags_from_val(self.a);
+            }
+            _ => (), // NOP
+        }
     }
 
-    pub fn NMI(&mut self, bus: &mut Bus) {
-        self.stack_push_pc(bus);
-        self.PHP(bus);
-		self.pc = 0xFFF9;
-		self.instr = Instructions::JMP;
-		self.addr = Addressing::ABS;
-        //self.JMP(bus, 0xFFFA);
+    fn rmw_op(&mut self, val: u8) -> u8 {
+        match self.instr {
+            Instructions::ASL => self.ASL(val),
+            Instructions::LSR => self.LSR(val),
+            Instructions::ROL => self.ROL(val),
+            Instructions::ROR => self.ROR(val),
+            Instructions::INC => self.flags_from_val(val.wrapping_add(1)),
+            Instructions::DEC => self.flags_from_val(val.wrapping_sub(1)),
+            // --------------- UNOFFICIAL --------------------
+            Instructions::SLO => {
+                let res = self.ASL(val);
+                self.a |= res;
+                self.flags_from_val(self.a);
+                res
+            }
+            Instructions::RLA => {
+                let res = self.ROL(val);
+                self.a &= res;
+                self.flags_from_val(self.a);
+                res
+            }
+            Instructions::SRE => {
+                let res = self.LSR(val);
+                self.a ^= res;
+                self.flags_from_val(self.a);
+                res
+            }
+            Instructions::RRA => {
+                let res = self.ROR(val);
+                self.ADC(res, self.c as u8);
+                res
+            }
+            Instructions::DCP => {
+                let res = val.wrapping_sub(1);
+                self.compare(self.a, res);
+                res
+            }
+            Instructions::ISC => {
+                let res = val.wrapping_add(1);
+                self.SBC(res);
+                res
+            }
+            _ => val,
+        }
     }
 
-    pub fn BRK(&mut self, bus: &mut Bus) {
-        self.JMP(bus, 0xFFFE);
+    fn write_op<B: CpuBus>(&mut self, bus: &mut B) {
+        let val = match self.instr {
+            Instructions::STA => self.a,
+            Instructions::STX => self.x,
+            Instructions::STY => self.y,
+            Instructions::SAX => self.a & self.x,
+            _ => {
+                // SHA/SHX/SHY/TAS store the register ANDed with the base address's
+                // high byte + 1, and a page cross replaces the high byte with the value
+                if self.instr == Instructions::TAS {
+                    self.stack_pointer = self.a & self.x;
+                }
+                let reg = match self.instr {
+                    Instructions::SHX => self.x,
+                    Instructions::SHY => self.y,
+                    Instructions::TAS => self.stack_pointer,
+                    _ => self.a & self.x,
+                };
+                let base_high =
+                    ((self.target_addr >> 8) as u8).wrapping_sub(self.page_crossed as u8);
+                let val = reg & base_high.wrapping_add(1);
+                if self.page_crossed {
+                    self.target_addr = (self.target_addr & 0x00FF) | (val as u16) << 8;
+                }
+                val
+            }
+        };
+        bus.cpu_write(self.target_addr, val);
     }
 
-    pub fn JMP(&mut self, bus: &mut Bus, addr: u16) {
-        self.pc = combine_low_high(bus.cpu_read_16(addr), bus.cpu_read_16(addr + 1));
+    pub fn branch_taken(&self) -> bool {
+        match self.instr {
+            Instructions::BCC => !self.c,
+            Instructions::BCS => self.c,
+            Instructions::BEQ => self.z,
+            Instructions::BMI => self.n,
+            Instructions::BNE => !self.z,
+            Instructions::BPL => !self.n,
+            Instructions::BVC => !self.o,
+            Instructions::BVS => self.o,
+            _ => false,
+        }
     }
 
-    pub fn PHP(&mut self, bus: &mut Bus) {
+    // --------------- INSTRUCTIONS --------------------
+
+    pub fn PHP<B: CpuBus>(&mut self, bus: &mut B) {
         let all_flags = set_u8_bit(self.flags_to_byte(), 4, 1);
         self.stack_push(all_flags, bus);
     }
 
-    pub fn PLP(&mut self, bus: &mut Bus) {
+    pub fn PLP<B: CpuBus>(&mut self, bus: &mut B) {
         let all_flags = self.stack_pull(bus);
-        self.n = get_u8_bit(all_flags, 7) == 1;
-        self.o = get_u8_bit(all_flags, 6) == 1;
-        self.b = get_u8_bit(all_flags, 4) == 1;
-        self.d = get_u8_bit(all_flags, 3) == 1;
-        self.i = get_u8_bit(all_flags, 2) == 1;
-        self.z = get_u8_bit(all_flags, 1) == 1;
-        self.c = get_u8_bit(all_flags, 0) == 1;
+        self.flags_from_byte(all_flags);
     }
 
     pub fn ADC(&mut self, b: u8, c: u8) {
@@ -120,29 +818,49 @@ impl Cpu {
         self.a = result2;
     }
 
-    // --------------- REGISTERS --------------------
+    // A - b is A + !b + C in two's complement
+    pub fn SBC(&mut self, b: u8) {
+        self.ADC(!b, self.c as u8);
+    }
 
-    pub fn stack_push_pc(&mut self, bus: &mut Bus) {
-        let low = self.pc as u8;
-        let high = (self.pc >> 8) as u8;
-        self.stack_push(high, bus);
-        self.stack_push(low, bus);
+    pub fn compare(&mut self, reg: u8, val: u8) {
+        self.flag_negative_from_val(reg.wrapping_sub(val));
+        self.flag_zero(reg == val);
+        self.flag_carry(reg >= val);
     }
 
-    pub fn stack_pull_pc(&mut self, bus: &mut Bus) {
-        let low = self.stack_pull(bus);
-        let high = self.stack_pull(bus);
-        self.pc = combine_low_high(low, high);
+    pub fn ASL(&mut self, val: u8) -> u8 {
+        self.flag_carry(get_u8_bit(val, 7) == 1);
+        self.flags_from_val(val << 1)
     }
 
-    pub fn stack_push(&mut self, val: u8, bus: &mut Bus) {
-        bus.cpu_write_16(self.stack_pointer_to_addr(), val);
+    pub fn LSR(&mut self, val: u8) -> u8 {
+        self.flag_carry(get_u8_bit(val, 0) == 1);
+        self.flags_from_val(val >> 1)
+    }
+
+    pub fn ROL(&mut self, val: u8) -> u8 {
+        let carry = self.c as u8;
+        self.flag_carry(get_u8_bit(val, 7) == 1);
+        self.flags_from_val(val << 1 | carry)
+    }
+
+    pub fn ROR(&mut self, val: u8) -> u8 {
+        let carry = self.c as u8;
+        self.flag_carry(get_u8_bit(val, 0) == 1);
+        self.flags_from_val(val >> 1 | carry << 7)
+    }
+
+    // --------------- REGISTERS --------------------
+
+    pub fn stack_push<B: CpuBus>(&mut self, val: u8, bus: &mut B) {
+        bus.cpu_write(self.stack_pointer_to_addr(), val);
         self.stack_pointer = self.stack_pointer.wrapping_sub(1);
     }
 
-    pub fn stack_pull(&mut self, bus: &mut Bus) -> u8 {
+    pub fn stack_pull<B: CpuBus>(&mut self, bus: &mut B) -> u8 {
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
-        bus.cpu_read_16(self.stack_pointer_to_addr())
+        bus.cpu_read(self.stack_pointer_to_addr())
     }
 
     pub fn stack_pointer_to_addr(&mut self) -> u16 {
@@ -171,7 +889,7 @@ impl Cpu {
 
     // --------------- FLAGS --------------------
 
-    pub fn flags_to_byte(&mut self) -> u8 {
+    pub fn flags_to_byte(&self) -> u8 {
         (self.n as u8) << 7
 		| (self.o as u8) << 6
 		| 1 << 5
@@ -183,6 +901,16 @@ impl Cpu {
 		| (self.c as u8)
     }
 
+    pub fn flags_from_byte(&mut self, all_flags: u8) {
+        self.n = get_u8_bit(all_flags, 7) == 1;
+        self.o = get_u8_bit(all_flags, 6) == 1;
+        self.b = get_u8_bit(all_flags, 4) == 1;
+        self.d = get_u8_bit(all_flags, 3) == 1;
+        self.i = get_u8_bit(all_flags, 2) == 1;
+        self.z = get_u8_bit(all_flags, 1) == 1;
+        self.c = get_u8_bit(all_flags, 0) == 1;
+    }
+
     pub fn flag_interrupt(&mut self, interrupt: bool) {
         self.i = interrupt;
     }
@@ -227,366 +955,19 @@ impl Cpu {
         }
     }
 
+    // Signed overflow: both inputs share a sign that the result doesn't
     pub fn flag_overflow_from_vals(&mut self, b: u8, c: u8) {
-        let a = self.a as i8;
-        let b = b as i8;
-        let c = c as i8;
-        if (a >= 0 && b >= 0 && c <= 0) || (a <= 0 && b <= 0 && c >= 0) {
-            self.o = true;
-        } else {
-            self.o = false;
-        }
+        self.o = (self.a ^ c) & (b ^ c) & 0x80 != 0;
     }
 
-    // --------------- END --------------------
-
-    #[rustfmt::skip]
-    pub fn execute_instruction(&mut self, bus: &mut Bus, ppu: &mut Ppu) {
-		if self.i {
-		}
-		// --------------- ADRESSING --------------------
-        let target_addr = match self.addr {
-            Addressing::IMP | Addressing::ACC => {self.pc += 1; 0},
-            Addressing::IMM => { self.pc += 2; self.pc - 1 },
-            Addressing::ZPG => { self.pc += 2; bus.read_single(self.pc) as u16 },
-            Addressing::ZPX => { self.pc += 2; bus.read_single(self.pc).wrapping_add(self.x) as u16 },
-            Addressing::ZPY => { self.pc += 2; bus.read_single(self.pc).wrapping_add(self.y) as u16 },
-            Addressing::ABS => { self.pc += 3; bus.read_double(self.pc) as u16 } ,
-            Addressing::ABX => { self.pc += 3; bus.read_double(self.pc).wrapping_add(self.x as u16) as u16 },
-            Addressing::ABY => { self.pc += 3; bus.read_double(self.pc).wrapping_add(self.y as u16) as u16 },
-            Addressing::IDX => {
-                self.pc += 2;
-                let inline_addr = bus.read_single(self.pc).wrapping_add(self.x);
-                let low = bus.cpu_read_8(inline_addr);
-                let high = bus.cpu_read_8(inline_addr.wrapping_add(1));
-                combine_low_high(low, high)
-            }
-            Addressing::IDY => {
-                self.pc += 2;
-                let inline_addr = bus.read_single(self.pc);
-                let low = bus.cpu_read_8(inline_addr);
-                let high = bus.cpu_read_8(inline_addr.wrapping_add(1));
-                combine_low_high(low, high).wrapping_add(self.y as u16)
-            }
-            Addressing::REL => { self.pc += 2; self.pc - 1 },
-            Addressing::IND => {
-                self.pc += 3;
-                let inline_addr = bus.read_double(self.pc);
-                let low = bus.cpu_read_16(inline_addr);
-				let high_addr = ((inline_addr as u8).wrapping_add(1) as u16) | (inline_addr & 0xFF00); // Increment low nibble only
-                let high = bus.cpu_read_16(high_addr);
-                combine_low_high(low, high)
-            },
-            _ => panic!("{}", ERR_ADDR),
-        };
-
-        let target_val = bus.cpu_read_16_ppu_regs(target_addr, ppu);
-
-		// --------------- INSTRUCTIONS --------------------
-        match self.instr {
-            Instructions::ADC => {
-				self.ADC(target_val, self.c as u8);
-            }
-            Instructions::SBC => {
-				self.ADC((0 as u8).wrapping_sub(target_val), (0 as u8).wrapping_sub(!self.c as u8));
-            }
-            Instructions::AND | Instructions::EOR | Instructions::ORA => {
-                let op: &dyn Fn(u8, u8) -> u8 = match self.instr { Instructions::AND => &u8_and, Instructions::ORA => &u8_or, Instructions::EOR => &u8_xor, _ => panic!() };
-                self.a = op(self.a, target_val);
-                self.flag_zero_from_val(self.a); self.flag_negative_from_val(self.a);
-            }
-            Instructions::ASL | Instructions::LSR | Instructions::ROL | Instructions::ROR => {
-                let op: &dyn Fn(u8) -> u8 = match self.instr { Instructions::ASL | Instructions::ROL => &u8_shl, Instructions::LSR | Instructions::ROR => &u8_shr, _ => panic!() };
-                let mut val: u8 = if self.addr == Addressing::ACC { self.a } else { target_val };
-                let bit_test: u8 = match self.instr { Instructions::ASL | Instructions::ROL => 0x80, Instructions::LSR | Instructions::ROR => 0x01, _ => panic!() };
-				let new_carry = val & bit_test == bit_test;
-				let carry: u8 = match self.instr { Instructions::ROL => self.c as u8, Instructions::ROR => (self.c as u8) << 7, _ => 0 };
-				val = op(val) | carry;
-				self.flag_zero_from_val(val); self.flag_negative_from_val(val); self.flag_carry(new_carry);
-				if self.addr == Addressing::ACC { self.a = val; } else { bus.cpu_write_16_ppu_regs(target_addr, val, ppu); };
-            }
-            Instructions::BCC | Instructions::BCS | Instructions::BEQ | Instructions::BMI | Instructions::BNE | Instructions::BPL | Instructions::BVC | Instructions::BVS => {
-                let can_branch = match self.instr { Instructions::BCC => !self.c, Instructions::BCS => self.c, Instructions::BEQ => self.z, Instructions::BMI => self.n, Instructions::BNE => !self.z, Instructions::BPL => !self.n, Instructions::BVC => !self.o, Instructions::BVS => self.o, _ => panic!() };
-                if can_branch {
-					self.pc = self.pc.wrapping_add_signed(target_val as i8 as i16);
-                }
-            }
-            Instructions::BIT => {
-                let res = target_val & self.a;
-                self.flag_zero_from_val(res); self.flag_negative_from_val(target_val); self.flag_overflow(get_u8_bit(target_val, 6) == 1);
-            }
-            Instructions::BRK => {
-                self.stack_push_pc(bus);
-                self.PHP(bus);
-                self.flag_break(true);
-                self.BRK(bus);
-            }
-            Instructions::CLC => self.flag_carry(false), Instructions::CLD => self.flag_decimal(false), Instructions::CLI => self.flag_interrupt(false), Instructions::CLV => self.flag_overflow(false),
-            Instructions::SEC => self.flag_carry(true), Instructions::SED => self.flag_decimal(true), Instructions::SEI => self.flag_interrupt(true),
-            Instructions::CMP | Instructions::CPX | Instructions::CPY => {
-                let reg = match self.instr { Instructions::CMP => self.a, Instructions::CPX => self.x, Instructions::CPY => self.y, _ => panic!()};
-                self.flag_negative_from_val(reg.wrapping_sub(target_val)); self.flag_zero(reg == target_val); self.flag_carry(reg >= target_val);
-            }
-            Instructions::DEC | Instructions::DEX | Instructions::DEY | Instructions::INC | Instructions::INX | Instructions::INY => {
-                let res = match self.instr { Instructions::DEC => bus.DEC(target_addr), Instructions::INC => bus.INC(target_addr), Instructions::DEX => self.DEX(), Instructions::DEY => self.DEY(), Instructions::INX => self.INX(), Instructions::INY => self.INY(), _ => panic!() };
-                self.flag_negative_from_val(res); self.flag_zero_from_val(res);
-            }
-            Instructions::JMP | Instructions::JSR => {
-                if self.instr == Instructions::JSR { self.pc -= 1; self.stack_push_pc(bus); }
-                self.pc = target_addr;
-            }
-            Instructions::RTS => {
-                self.stack_pull_pc(bus);
-				self.pc += 1;
-            }
-            Instructions::LDA | Instructions::LDX | Instructions::LDY => {
-                match self.instr { Instructions::LDA => self.a = target_val, Instructions::LDX => self.x = target_val, Instructions::LDY => self.y = target_val, _ => panic!() };
-                self.flag_zero_from_val(target_val); self.flag_negative_from_val(target_val);
-            }
-            Instructions::NOP => (),
-            Instructions::PHA => {
-                self.stack_push(self.a, bus);
-            }
-            Instructions::PLA => {
-                self.a = self.stack_pull(bus);
-				self.flag_zero_from_val(self.a); self.flag_negative_from_val(self.a)
-            }
-            Instructions::PHP => {
-                self.PHP(bus);
-            },
-            Instructions::PLP => {
-                self.PLP(bus);
-            },
-            Instructions::RTI => {
-                self.PLP(bus);
-                self.stack_pull_pc(bus);
-            },
-            Instructions::STA => {
-				bus.cpu_write_16_ppu_regs(target_addr, self.a, ppu);
-            },
-            Instructions::STX => {
-				bus.cpu_write_16_ppu_regs(target_addr, self.x, ppu);
-            },
-            Instructions::STY => {
-				bus.cpu_write_16_ppu_regs(target_addr, self.y, ppu);
-            },
-            Instructions::TAX | Instructions::TSX => {
-                self.x = match self.instr { Instructions::TAX => self.a, Instructions::TSX => self.stack_pointer, _ => panic!() };
-                self.flag_zero_from_val(self.x); self.flag_negative_from_val(self.x);
-            },
-            Instructions::TXA | Instructions::TYA => {
-                self.a = match self.instr { Instructions::TXA => self.x, Instructions::TYA => self.y, _ => panic!() };
-                self.flag_zero_from_val(self.a); self.flag_negative_from_val(self.a);
-            },
-            Instructions::TAY => {
-                self.y = self.a;
-                self.flag_zero_from_val(self.y); self.flag_negative_from_val(self.y);
-            },
-            Instructions::TXS => {
-                self.stack_pointer = self.x;
-            },
-            _ => panic!("{}", ERR_OP),
-        }
-
-        //println!("prev target val: {:02x}", target_val);
-        //println!("prev target addr: {:04x}", target_addr);
-    }
-
-    #[rustfmt::skip]
-    pub fn load_instruction(&mut self, bus: &mut Bus) -> (u8, u8, u8, u8, u8, u8, u16) {
-        let cycles: u8;
-		let opcode = bus.cpu_read_16(self.pc);
-
-        match opcode {
-            0x69 => {self.instr = Instructions::ADC; self.addr = Addressing::IMM; cycles = 2},
-            0x65 => {self.instr = Instructions::ADC; self.addr = Addressing::ZPG; cycles = 3},
-            0x75 => {self.instr = Instructions::ADC; self.addr = Addressing::ZPX; cycles = 4},
-            0x6D => {self.instr = Instructions::ADC; self.addr = Addressing::ABS; cycles = 4},
-            0x7D => {self.instr = Instructions::ADC; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x79 => {self.instr = Instructions::ADC; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0x61 => {self.instr = Instructions::ADC; self.addr = Addressing::IDX; cycles = 6},
-            0x71 => {self.instr = Instructions::ADC; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0xE9 => {self.instr = Instructions::SBC; self.addr = Addressing::IMM; cycles = 2},
-            0xE5 => {self.instr = Instructions::SBC; self.addr = Addressing::ZPG; cycles = 3},
-            0xF5 => {self.instr = Instructions::SBC; self.addr = Addressing::ZPX; cycles = 4},
-            0xED => {self.instr = Instructions::SBC; self.addr = Addressing::ABS; cycles = 4},
-            0xFD => {self.instr = Instructions::SBC; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0xF9 => {self.instr = Instructions::SBC; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0xE1 => {self.instr = Instructions::SBC; self.addr = Addressing::IDX; cycles = 6},
-            0xF1 => {self.instr = Instructions::SBC; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0x29 => {self.instr = Instructions::AND; self.addr = Addressing::IMM; cycles = 2},
-            0x25 => {self.instr = Instructions::AND; self.addr = Addressing::ZPG; cycles = 3},
-            0x35 => {self.instr = Instructions::AND; self.addr = Addressing::ZPX; cycles = 4},
-            0x2D => {self.instr = Instructions::AND; self.addr = Addressing::ABS; cycles = 4},
-            0x3D => {self.instr = Instructions::AND; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x39 => {self.instr = Instructions::AND; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0x21 => {self.instr = Instructions::AND; self.addr = Addressing::IDX; cycles = 6},
-            0x31 => {self.instr = Instructions::AND; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0x09 => {self.instr = Instructions::ORA; self.addr = Addressing::IMM; cycles = 2},
-            0x05 => {self.instr = Instructions::ORA; self.addr = Addressing::ZPG; cycles = 3},
-            0x15 => {self.instr = Instructions::ORA; self.addr = Addressing::ZPX; cycles = 4},
-            0x0D => {self.instr = Instructions::ORA; self.addr = Addressing::ABS; cycles = 4},
-            0x1D => {self.instr = Instructions::ORA; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x19 => {self.instr = Instructions::ORA; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x01 => {self.instr = Instructions::ORA; self.addr = Addressing::IDX; cycles = 6},
-            0x11 => {self.instr = Instructions::ORA; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0x49 => {self.instr = Instructions::EOR; self.addr = Addressing::IMM; cycles = 2},
-            0x45 => {self.instr = Instructions::EOR; self.addr = Addressing::ZPG; cycles = 3},
-            0x55 => {self.instr = Instructions::EOR; self.addr = Addressing::ZPX; cycles = 4},
-            0x4D => {self.instr = Instructions::EOR; self.addr = Addressing::ABS; cycles = 4},
-            0x5D => {self.instr = Instructions::EOR; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x59 => {self.instr = Instructions::EOR; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0x41 => {self.instr = Instructions::EOR; self.addr = Addressing::IDX; cycles = 6},
-            0x51 => {self.instr = Instructions::EOR; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0x0A => {self.instr = Instructions::ASL; self.addr = Addressing::ACC; cycles = 2},
-            0x06 => {self.instr = Instructions::ASL; self.addr = Addressing::ZPG; cycles = 5},
-            0x16 => {self.instr = Instructions::ASL; self.addr = Addressing::ZPX; cycles = 6},
-            0x0E => {self.instr = Instructions::ASL; self.addr = Addressing::ABS; cycles = 6},
-            0x1E => {self.instr = Instructions::ASL; self.addr = Addressing::ABX; cycles = 7},
-
-            0x4A => {self.instr = Instructions::LSR; self.addr = Addressing::ACC; cycles = 2},
-            0x46 => {self.instr = Instructions::LSR; self.addr = Addressing::ZPG; cycles = 5},
-            0x56 => {self.instr = Instructions::LSR; self.addr = Addressing::ZPX; cycles = 6},
-            0x4E => {self.instr = Instructions::LSR; self.addr = Addressing::ABS; cycles = 6},
-            0x5E => {self.instr = Instructions::LSR; self.addr = Addressing::ABX; cycles = 7},
-
-            0x2A => {self.instr = Instructions::ROL; self.addr = Addressing::ACC; cycles = 2},
-            0x26 => {self.instr = Instructions::ROL; self.addr = Addressing::ZPG; cycles = 5},
-            0x36 => {self.instr = Instructions::ROL; self.addr = Addressing::ZPX; cycles = 6},
-            0x2E => {self.instr = Instructions::ROL; self.addr = Addressing::ABS; cycles = 6},
-            0x3E => {self.instr = Instructions::ROL; self.addr = Addressing::ABX; cycles = 7},
-
-            0x6A => {self.instr = Instructions::ROR; self.addr = Addressing::ACC; cycles = 2},
-            0x66 => {self.instr = Instructions::ROR; self.addr = Addressing::ZPG; cycles = 5},
-            0x76 => {self.instr = Instructions::ROR; self.addr = Addressing::ZPX; cycles = 6},
-            0x6E => {self.instr = Instructions::ROR; self.addr = Addressing::ABS; cycles = 6},
-            0x7E => {self.instr = Instructions::ROR; self.addr = Addressing::ABX; cycles = 7},
-
-            0x90 => {self.instr = Instructions::BCC; self.addr = Addressing::REL; cycles = if self.c { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0xB0 => {self.instr = Instructions::BCS; self.addr = Addressing::REL; cycles = if !self.c { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0xF0 => {self.instr = Instructions::BEQ; self.addr = Addressing::REL; cycles = if !self.z { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0x30 => {self.instr = Instructions::BMI; self.addr = Addressing::REL; cycles = if !self.n { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0xD0 => {self.instr = Instructions::BNE; self.addr = Addressing::REL; cycles = if self.z { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0x10 => {self.instr = Instructions::BPL; self.addr = Addressing::REL; cycles = if self.n { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0x50 => {self.instr = Instructions::BVC; self.addr = Addressing::REL; cycles = if self.o { 2 } else { 3 + bus.cross_rel(self.pc) } },
-            0x70 => {self.instr = Instructions::BVS; self.addr = Addressing::REL; cycles = if !self.o { 2 } else { 3 + bus.cross_rel(self.pc) } },
-
-            0x24 => {self.instr = Instructions::BIT; self.addr = Addressing::ZPG; cycles = 3},
-            0x2C => {self.instr = Instructions::BIT; self.addr = Addressing::ABS; cycles = 4},
-
-            0x00 => {self.instr = Instructions::BRK; self.addr = Addressing::IMP; cycles = 7},
-            0x40 => {self.instr = Instructions::RTI; self.addr = Addressing::IMP; cycles = 6},
-
-            0x18 => {self.instr = Instructions::CLC; self.addr = Addressing::IMP; cycles = 2},
-            0xD8 => {self.instr = Instructions::CLD; self.addr = Addressing::IMP; cycles = 2},
-            0x58 => {self.instr = Instructions::CLI; self.addr = Addressing::IMP; cycles = 2},
-            0xB8 => {self.instr = Instructions::CLV; self.addr = Addressing::IMP; cycles = 2},
-            0x38 => {self.instr = Instructions::SEC; self.addr = Addressing::IMP; cycles = 2},
-            0xF8 => {self.instr = Instructions::SED; self.addr = Addressing::IMP; cycles = 2},
-            0x78 => {self.instr = Instructions::SEI; self.addr = Addressing::IMP; cycles = 2},
-
-            0xC9 => {self.instr = Instructions::CMP; self.addr = Addressing::IMM; cycles = 2},
-            0xC5 => {self.instr = Instructions::CMP; self.addr = Addressing::ZPG; cycles = 3},
-            0xD5 => {self.instr = Instructions::CMP; self.addr = Addressing::ZPX; cycles = 4},
-            0xCD => {self.instr = Instructions::CMP; self.addr = Addressing::ABS; cycles = 4},
-            0xDD => {self.instr = Instructions::CMP; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0xD9 => {self.instr = Instructions::CMP; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0xC1 => {self.instr = Instructions::CMP; self.addr = Addressing::IDX; cycles = 6},
-            0xD1 => {self.instr = Instructions::CMP; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0xE0 => {self.instr = Instructions::CPX; self.addr = Addressing::IMM; cycles = 2},
-            0xE4 => {self.instr = Instructions::CPX; self.addr = Addressing::ZPG; cycles = 3},
-            0xEC => {self.instr = Instructions::CPX; self.addr = Addressing::ABS; cycles = 4},
-
-            0xC0 => {self.instr = Instructions::CPY; self.addr = Addressing::IMM; cycles = 2},
-            0xC4 => {self.instr = Instructions::CPY; self.addr = Addressing::ZPG; cycles = 3},
-            0xCC => {self.instr = Instructions::CPY; self.addr = Addressing::ABS; cycles = 4},
-
-            0xC6 => {self.instr = Instructions::DEC; self.addr = Addressing::ZPG; cycles = 5},
-            0xD6 => {self.instr = Instructions::DEC; self.addr = Addressing::ZPX; cycles = 6},
-            0xCE => {self.instr = Instructions::DEC; self.addr = Addressing::ABS; cycles = 6},
-            0xDE => {self.instr = Instructions::DEC; self.addr = Addressing::ABX; cycles = 7},
-
-            0xE6 => {self.instr = Instructions::INC; self.addr = Addressing::ZPG; cycles = 5},
-            0xF6 => {self.instr = Instructions::INC; self.addr = Addressing::ZPX; cycles = 6},
-            0xEE => {self.instr = Instructions::INC; self.addr = Addressing::ABS; cycles = 6},
-            0xFE => {self.instr = Instructions::INC; self.addr = Addressing::ABX; cycles = 7},
-
-            0xE8 => {self.instr = Instructions::INX; self.addr = Addressing::IMP; cycles = 2},
-            0xC8 => {self.instr = Instructions::INY; self.addr = Addressing::IMP; cycles = 2},
-
-            0xCA => {self.instr = Instructions::DEX; self.addr = Addressing::IMP; cycles = 2},
-            0x88 => {self.instr = Instructions::DEY; self.addr = Addressing::IMP; cycles = 2},
-
-            0x4C => {self.instr = Instructions::JMP; self.addr = Addressing::ABS; cycles = 3},
-            0x6C => {self.instr = Instructions::JMP; self.addr = Addressing::IND; cycles = 5},
-
-            0x20 => {self.instr = Instructions::JSR; self.addr = Addressing::ABS; cycles = 6},
-
-            0x60 => {self.instr = Instructions::RTS; self.addr = Addressing::IMP; cycles = 6},
-
-            0xA9 => {self.instr = Instructions::LDA; self.addr = Addressing::IMM; cycles = 2},
-            0xA5 => {self.instr = Instructions::LDA; self.addr = Addressing::ZPG; cycles = 3},
-            0xB5 => {self.instr = Instructions::LDA; self.addr = Addressing::ZPX; cycles = 4},
-            0xAD => {self.instr = Instructions::LDA; self.addr = Addressing::ABS; cycles = 4},
-            0xBD => {self.instr = Instructions::LDA; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-            0xB9 => {self.instr = Instructions::LDA; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-            0xA1 => {self.instr = Instructions::LDA; self.addr = Addressing::IDX; cycles = 6},
-            0xB1 => {self.instr = Instructions::LDA; self.addr = Addressing::IDY; cycles = 5 + bus.cross_idy(self.pc, self.y)},
-
-            0xA0 => {self.instr = Instructions::LDY; self.addr = Addressing::IMM; cycles = 2},
-            0xA4 => {self.instr = Instructions::LDY; self.addr = Addressing::ZPG; cycles = 3},
-            0xB4 => {self.instr = Instructions::LDY; self.addr = Addressing::ZPX; cycles = 4},
-            0xAC => {self.instr = Instructions::LDY; self.addr = Addressing::ABS; cycles = 4},
-            0xBC => {self.instr = Instructions::LDY; self.addr = Addressing::ABX; cycles = 4 + bus.cross_abs(self.pc, self.x)},
-
-            0xA2 => {self.instr = Instructions::LDX; self.addr = Addressing::IMM; cycles = 2},
-            0xA6 => {self.instr = Instructions::LDX; self.addr = Addressing::ZPG; cycles = 3},
-            0xB6 => {self.instr = Instructions::LDX; self.addr = Addressing::ZPY; cycles = 4},
-            0xAE => {self.instr = Instructions::LDX; self.addr = Addressing::ABS; cycles = 4},
-            0xBE => {self.instr = Instructions::LDX; self.addr = Addressing::ABY; cycles = 4 + bus.cross_abs(self.pc, self.y)},
-
-            0xEA => {self.instr = Instructions::NOP; self.addr = Addressing::IMP; cycles = 2},
-
-            0x48 => {self.instr = Instructions::PHA; self.addr = Addressing::IMP; cycles = 3},
-            0x68 => {self.instr = Instructions::PLA; self.addr = Addressing::IMP; cycles = 4},
-            0x08 => {self.instr = Instructions::PHP; self.addr = Addressing::IMP; cycles = 3},
-            0x28 => {self.instr = Instructions::PLP; self.addr = Addressing::IMP; cycles = 4},
-
-            0x85 => {self.instr = Instructions::STA; self.addr = Addressing::ZPG; cycles = 3},
-            0x95 => {self.instr = Instructions::STA; self.addr = Addressing::ZPX; cycles = 4},
-            0x8D => {self.instr = Instructions::STA; self.addr = Addressing::ABS; cycles = 4},
-            0x9D => {self.instr = Instructions::STA; self.addr = Addressing::ABX; cycles = 5},
-            0x99 => {self.instr = Instructions::STA; self.addr = Addressing::ABY; cycles = 5},
-            0x81 => {self.instr = Instructions::STA; self.addr = Addressing::IDX; cycles = 6},
-            0x91 => {self.instr = Instructions::STA; self.addr = Addressing::IDY; cycles = 6},
-
-            0x86 => {self.instr = Instructions::STX; self.addr = Addressing::ZPG; cycles = 3},
-            0x96 => {self.instr = Instructions::STX; self.addr = Addressing::ZPY; cycles = 4},
-            0x8E => {self.instr = Instructions::STX; self.addr = Addressing::ABS; cycles = 4},
-
-            0x84 => {self.instr = Instructions::STY; self.addr = Addressing::ZPG; cycles = 3},
-            0x94 => {self.instr = Instructions::STY; self.addr = Addressing::ZPX; cycles = 4},
-            0x8C => {self.instr = Instructions::STY; self.addr = Addressing::ABS; cycles = 4},
-
-            0xAA => {self.instr = Instructions::TAX; self.addr = Addressing::IMP; cycles = 2},
-            0xA8 => {self.instr = Instructions::TAY; self.addr = Addressing::IMP; cycles = 2},
-            0x8A => {self.instr = Instructions::TXA; self.addr = Addressing::IMP; cycles = 2},
-            0x98 => {self.instr = Instructions::TYA; self.addr = Addressing::IMP; cycles = 2},
-
-            0xBA => {self.instr = Instructions::TSX; self.addr = Addressing::IMP; cycles = 2},
-            0x9A => {self.instr = Instructions::TXS; self.addr = Addressing::IMP; cycles = 2},
-            _ => {std::thread::sleep(Duration::from_secs(1)); panic!("{}", ERR_OP)},
-        }
-
-        (cycles, self.flags_to_byte(), self.stack_pointer, self.a, self.x, self.y, self.pc)
+    pub fn flags_from_val(&mut self, val: u8) -> u8 {
+        self.flag_zero_from_val(val);
+        self.flag_negative_from_val(val);
+        val
     }
 
+    // --------------- END --------------------
+
     pub fn print_stack(&mut self, bus: &mut Bus) {
         let abs_pointer = self.stack_pointer_to_addr();
         for n in abs_pointer - 5..abs_pointer + 5 {
@@ -0,0 +1,137 @@
+use crate::bus::SystemBus;
+use crate::cartridge::CartridgeError;
+use crate::palette::Palette;
+use crate::Bus;
+use crate::Cpu;
+use crate::Ppu;
+
+// A whole console: cartridge, cpu, ppu and the bus between them. Frontends
+// and tools drive it through this instead of wiring the parts up by hand.
+pub struct Emulator {
+    pub bus: Bus,
+    pub cpu: Cpu,
+    pub ppu: Ppu,
+    // turns the framebuffer's NES colors into RGB
+    pub palette: Palette,
+    // the loaded rom, kept so a power cycle can start from a clean cartridge
+    rom: Vec<u8>,
+    audio: Vec<f32>,
+}
+
+impl Default for Emulator {
+    fn default() -> Emulator {
+        Emulator::new()
+    }
+}
+
+impl Emulator {
+    pub fn new() -> Emulator {
+        Emulator {
+            bus: Bus::new(),
+            cpu: Cpu::new(),
+            ppu: Ppu::new(),
+            palette: Palette::ntsc(),
+            rom: vec![],
+            audio: vec![],
+        }
+    }
+
+    // Insert a cartridge (an iNES/NES 2.0 image) and power the console on
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let mut bus = Bus::new();
+        bus.load_rom(rom)?;
+        self.rom = rom.to_vec();
+        self.bus = bus;
+        self.cpu = Cpu::new();
+        self.ppu = Ppu::new();
+        self.audio.clear();
+        self.cpu.Reset();
+        Ok(())
+    }
+
+    // One CPU cycle: three PPU dots, then the cpu's bus access
+    pub fn clock(&mut self) {
+        for _ in 0..3 {
+            self.ppu.tick(&mut self.bus);
+        }
+        self.cpu.step(&mut SystemBus {
+            bus: &mut self.bus,
+            ppu: &mut self.ppu,
+        });
+        self.bus.mapper.cpu_clock();
+    }
+
+    // Run until the current instruction (or interrupt sequence) is done,
+    // returning how many cycles it took, OAM DMA included
+    pub fn step_instruction(&mut self) -> u16 {
+        let mut cycles = 0;
+        loop {
+            self.clock();
+            cycles += 1;
+            if (self.cpu.cycle == 0 && self.cpu.stall == 0) || self.cpu.jammed {
+                return cycles;
+            }
+        }
+    }
+
+    // Run until the PPU finishes the frame it's on
+    pub fn run_frame(&mut self) {
+        let frame = self.ppu.frame;
+        while self.ppu.frame == frame {
+            self.clock();
+        }
+    }
+
+    // 256x240 NES colors (palette indices with the emphasis bits above them),
+    // row by row
+    pub fn framebuffer(&self) -> &[u16] {
+        &self.ppu.framebuffer
+    }
+
+    // The framebuffer through the palette, 3 bytes per pixel
+    pub fn framebuffer_rgb(&self) -> Vec<u8> {
+        let mut pixels = vec![0; self.ppu.framebuffer.len() * 3];
+        self.palette.to_rgb24(&self.ppu.framebuffer, &mut pixels);
+        pixels
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    // Samples produced since the last call. There's no APU yet, so for now
+    // this is always empty.
+    pub fn audio_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.audio)
+    }
+
+    // Buttons held on controller `port`, one bit each:
+    // A, B, Select, Start, Up, Down, Left, Right from bit 0 up.
+    // There are only two ports, so panics unless `port` is 0 or 1
+    pub fn set_buttons(&mut self, port: usize, state: u8) {
+        assert!(port < 2, "no controller port {}", port);
+        self.bus.input[port] = state;
+    }
+
+    // The reset button: memory and the cartridge keep their state
+    pub fn reset(&mut self) {
+        self.cpu.Reset();
+        self.ppu.reset();
+    }
+
+    // Switch the console off and on again with the same cartridge
+    pub fn power_cycle(&mut self) {
+        let rom = std::mem::take(&mut self.rom);
+        if rom.is_empty() {
+            self.bus = Bus::new();
+            self.cpu = Cpu::new();
+            self.ppu = Ppu::new();
+            self.audio.clear();
+            return;
+        }
+        if let Err(why) = self.load_rom(&rom) {
+            // it loaded fine the first time
+            panic!("{}", why);
+        }
+    }
+}
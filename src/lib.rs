@@ -0,0 +1,16 @@
+pub mod bus;
+pub mod cartridge;
+pub mod cpu;
+pub mod emulator;
+pub mod mapper;
+pub mod opcodes;
+pub mod palette;
+pub mod ppu;
+pub mod test_rom;
+pub mod trace;
+pub mod util;
+
+pub use bus::Bus;
+pub use cpu::Cpu;
+pub use emulator::Emulator;
+pub use ppu::Ppu;
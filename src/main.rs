@@ -1,38 +1,36 @@
-mod bus;
-mod cpu;
-mod ppu;
-mod testing;
-mod util;
-
-use crate::util::*;
-use bus::Bus;
-use cpu::Cpu;
-use ppu::Ppu;
-use testing::Testing;
-
-use csv::Reader;
+use nes::palette::Palette;
+use nes::ppu::{HEIGHT, WIDTH};
+use nes::Emulator;
+
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use std::fs::File;
-use std::thread::sleep;
+use sdl2::pixels::PixelFormatEnum;
 use std::time::{Duration, Instant};
 
 fn main() {
-    let mut bus = Bus::new();
-    let mut cpu = Cpu::new();
-    let mut ppu = Ppu::new();
-
-    let mut cycles_left = 0;
-    let mut cycles_frame: u128 = 0;
+    let mut emulator = Emulator::new();
 
-    bus.load_cartridge("/home/david/Downloads/dk.nes");
-    //bus.load_cartridge("/home/david/Documents/nes/src/testing/nestest.nes");
-    cpu.Reset(&mut bus);
-    let mut testing = Testing::new("/home/david/Documents/nes/src/testing/second_break/reset6.log");
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: nes <rom.nes> [palette.pal]");
+            std::process::exit(1);
+        }
+    };
+    let rom = match std::fs::read(&path) {
+        Ok(rom) => rom,
+        Err(why) => panic!("{}", why),
+    };
+    if let Err(why) = emulator.load_rom(&rom) {
+        panic!("{}", why);
+    }
+    // An optional .pal file replaces the built-in NTSC palette
+    if let Some(path) = std::env::args().nth(2) {
+        match Palette::from_path(&path) {
+            Ok(palette) => emulator.set_palette(palette),
+            Err(why) => panic!("{}", why),
+        }
+    }
 
     // --------------- SDL ------------------
 
@@ -46,20 +44,22 @@ fn main() {
         .unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
-    canvas.set_scale(3.0, 3.0);
     canvas.clear();
     canvas.present();
 
+    // The PPU renders into its own framebuffer, SDL only shows it
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, WIDTH as u32, HEIGHT as u32)
+        .unwrap();
+    let mut pixels = vec![0; WIDTH * HEIGHT * 3];
+
     // --------------- Inputs ------------------
     let mut event_pump = sdl_context.event_pump().unwrap();
     let mut input: u8 = 0;
     let mut start = Instant::now();
 
     loop {
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. }
@@ -101,32 +101,17 @@ fn main() {
             }
         }
 
-        bus.input = input;
-
-        // --------------- Instructions ------------------
-
-        while cycles_frame < 29780 {
-            if cycles_left == 1 {
-                // on the final cycle -> execute the previous instruction
-                cpu.execute_instruction(&mut bus, &mut ppu);
-                testing.check_vblank(&mut bus, &mut cpu);
-            } else if cycles_left == 0 {
-                // get a new instruction and wait for cycles_left
-                let (temp, p, sp, a, x, y, addr) = cpu.load_instruction(&mut bus);
-                cycles_left = temp;
-                //testing.test_log(&mut cpu, &mut ppu);
-                testing.cyc += cycles_left as u128;
-                cycles_frame += cycles_left as u128;
-            }
-            for m in 0..3 {
-                let temp = ppu.tick(&mut bus, &mut canvas, &mut cpu);
-                cycles_left += temp;
-                testing.cyc += temp as u128;
-            }
-            cycles_left -= 1;
-        }
+        emulator.set_buttons(0, input);
+
+        // --------------- Emulation ------------------
+
+        emulator.run_frame();
 
-        cycles_frame = 0;
+        emulator
+            .palette
+            .to_rgb24(emulator.framebuffer(), &mut pixels);
+        texture.update(None, &pixels, WIDTH * 3).unwrap();
+        canvas.copy(&texture, None, None).unwrap();
         canvas.present();
 
         // --------------- Timing ------------------
@@ -0,0 +1,69 @@
+mod axrom;
+mod cnrom;
+mod gxrom;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+pub use axrom::Axrom;
+pub use cnrom::Cnrom;
+pub use gxrom::Gxrom;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+pub use uxrom::Uxrom;
+
+use crate::cartridge::{Cartridge, CartridgeError};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+// A cartridge board. The Bus forwards every access in cartridge space
+// ($4020-$FFFF on the CPU side, $0000-$1FFF on the PPU side) to the mapper,
+// which owns the PRG/CHR memory and decides which banks are visible.
+pub trait Mapper {
+    // None where the board doesn't drive the data bus, which leaves the CPU
+    // reading open bus
+    fn cpu_read(&mut self, addr: u16) -> Option<u8>;
+    fn cpu_write(&mut self, addr: u16, val: u8);
+    fn ppu_read(&mut self, addr: u16) -> u8;
+    fn ppu_write(&mut self, addr: u16, val: u8);
+
+    // Sees every address the PPU puts on its bus, for boards that watch the
+    // address lines (e.g. MMC3 counting A12 rises)
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    // Called once per CPU cycle, for boards that care about M2 timing
+    fn cpu_clock(&mut self) {}
+
+    // Level of the cartridge IRQ line (true = asserted)
+    fn irq(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring;
+}
+
+pub fn from_cartridge(cartridge: Cartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
+    match cartridge.mapper {
+        0 => Ok(Box::new(Nrom::new(
+            cartridge.prg_rom,
+            cartridge.chr_rom,
+            cartridge.mirroring,
+        ))),
+        1 => Ok(Box::new(Mmc1::new(cartridge))),
+        2 => Ok(Box::new(Uxrom::new(cartridge))),
+        3 => Ok(Box::new(Cnrom::new(cartridge))),
+        4 => Ok(Box::new(Mmc3::new(cartridge))),
+        7 => Ok(Box::new(Axrom::new(cartridge))),
+        66 => Ok(Box::new(Gxrom::new(cartridge))),
+        _ => Err(CartridgeError::UnsupportedMapper(cartridge.mapper)),
+    }
+}
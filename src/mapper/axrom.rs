@@ -0,0 +1,69 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+use crate::util::*;
+
+const PRG_BANK_SIZE: usize = 0x8000;
+
+// Mapper 7: switchable 32K PRG bank, 8K CHR RAM, and bit 4 of the bank
+// register picks which nametable is used for single-screen mirroring.
+pub struct Axrom {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    bank: u8,
+    bus_conflicts: bool,
+}
+
+impl Axrom {
+    pub fn new(cartridge: Cartridge) -> Axrom {
+        Axrom {
+            chr: cartridge.chr_memory(),
+            chr_ram: cartridge.has_chr_ram(),
+            bank: 0,
+            // Only AMROM (submapper 2) has bus conflicts, ANROM/AOROM don't
+            bus_conflicts: cartridge.submapper == 2,
+            prg: cartridge.prg_rom,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        if addr >= 0x8000 {
+            let offset = (self.bank & 0x07) as usize * PRG_BANK_SIZE + (addr - 0x8000) as usize;
+            Some(self.prg[offset % self.prg.len()])
+        } else {
+            None
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            let val = if self.bus_conflicts {
+                val & self.cpu_read(addr).unwrap_or(val)
+            } else {
+                val
+            };
+            self.bank = val;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr & 0x1FFF) as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            self.chr[(addr & 0x1FFF) as usize % len] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if get_u8_bit(self.bank, 4) == 1 {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        }
+    }
+}
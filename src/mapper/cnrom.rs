@@ -0,0 +1,58 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+// Mapper 3: fixed PRG like NROM, switchable 8K CHR bank.
+pub struct Cnrom {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    bank: u8,
+    bus_conflicts: bool,
+    mirroring: Mirroring,
+}
+
+impl Cnrom {
+    pub fn new(cartridge: Cartridge) -> Cnrom {
+        Cnrom {
+            chr: cartridge.chr_memory(),
+            bank: 0,
+            // Submapper 1 marks boards without bus conflicts
+            bus_conflicts: cartridge.submapper != 1,
+            mirroring: cartridge.mirroring,
+            prg: cartridge.prg_rom,
+        }
+    }
+}
+
+impl Mapper for Cnrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        if addr >= 0x8000 {
+            Some(self.prg[(addr - 0x8000) as usize % self.prg.len()])
+        } else {
+            None
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            let val = if self.bus_conflicts {
+                val & self.cpu_read(addr).unwrap_or(val)
+            } else {
+                val
+            };
+            self.bank = val;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let offset = self.bank as usize * CHR_BANK_SIZE + (addr & 0x1FFF) as usize;
+        self.chr[offset % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _val: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
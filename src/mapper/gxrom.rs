@@ -0,0 +1,57 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+use crate::util::*;
+
+const PRG_BANK_SIZE: usize = 0x8000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+// Mapper 66: one register selecting a 32K PRG bank (bits 4-5) and an 8K CHR
+// bank (bits 0-1).
+pub struct Gxrom {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    bank: u8,
+    mirroring: Mirroring,
+}
+
+impl Gxrom {
+    pub fn new(cartridge: Cartridge) -> Gxrom {
+        Gxrom {
+            chr: cartridge.chr_memory(),
+            bank: 0,
+            mirroring: cartridge.mirroring,
+            prg: cartridge.prg_rom,
+        }
+    }
+}
+
+impl Mapper for Gxrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        if addr >= 0x8000 {
+            let bank = get_u8_bits(self.bank, 5, 4) as usize;
+            let offset = bank * PRG_BANK_SIZE + (addr - 0x8000) as usize;
+            Some(self.prg[offset % self.prg.len()])
+        } else {
+            None
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            // GNROM always has bus conflicts
+            self.bank = val & self.cpu_read(addr).unwrap_or(val);
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        let bank = get_u8_bits(self.bank, 1, 0) as usize;
+        let offset = bank * CHR_BANK_SIZE + (addr & 0x1FFF) as usize;
+        self.chr[offset % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, _addr: u16, _val: u8) {}
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,173 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+use crate::util::*;
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// Mapper 1: five serial writes to $8000-$FFFF load one of four internal
+// registers, selected by address bits 13 and 14 on the final write.
+pub struct Mmc1 {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    prg_ram: Vec<u8>,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_0: u8,
+    chr_1: u8,
+    prg_bank: u8,
+    cycle: u64,
+    last_write_cycle: Option<u64>,
+}
+
+impl Mmc1 {
+    pub fn new(cartridge: Cartridge) -> Mmc1 {
+        Mmc1 {
+            chr: cartridge.chr_memory(),
+            chr_ram: cartridge.has_chr_ram(),
+            prg_ram: cartridge.prg_ram(),
+            prg: cartridge.prg_rom,
+            shift: 0,
+            shift_count: 0,
+            control: 0x0C,
+            chr_0: 0,
+            chr_1: 0,
+            prg_bank: 0,
+            cycle: 0,
+            last_write_cycle: None,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = val,
+            0xA000..=0xBFFF => self.chr_0 = val,
+            0xC000..=0xDFFF => self.chr_1 = val,
+            _ => self.prg_bank = val,
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        get_u8_bit(self.prg_bank, 4) == 0
+    }
+
+    // SUROM and friends use CHR bit 4 to select the upper 256K of PRG
+    fn prg_outer_bank(&self) -> usize {
+        if self.prg.len() > 0x40000 {
+            (get_u8_bit(self.chr_0, 4) as usize) << 4
+        } else {
+            0
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let last_bank = (self.prg.len() / PRG_BANK_SIZE).clamp(1, 16) - 1;
+        let select = (self.prg_bank & 0x0F) as usize;
+        let high = addr >= 0xC000;
+        let bank = match get_u8_bits(self.control, 3, 2) {
+            0 | 1 => (select & 0x0E) | high as usize,
+            2 => {
+                if high {
+                    select
+                } else {
+                    0
+                }
+            }
+            _ => {
+                if high {
+                    last_bank
+                } else {
+                    select
+                }
+            }
+        };
+        let bank = self.prg_outer_bank() | bank;
+        (bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1))) % self.prg.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let high = addr >= 0x1000;
+        let bank = if get_u8_bit(self.control, 4) == 0 {
+            (self.chr_0 & 0x1E) as usize | high as usize
+        } else if high {
+            self.chr_1 as usize
+        } else {
+            self.chr_0 as usize
+        };
+        (bank * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Some(self.prg_ram[(addr - 0x6000) as usize % self.prg_ram.len()])
+            }
+            0x8000..=0xFFFF => Some(self.prg[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr - 0x6000) as usize % len] = val;
+            }
+            0x8000..=0xFFFF => {
+                // The serial port ignores a write on the cycle right after
+                // another one, e.g. the second write of an INC/DEC
+                let consecutive = self
+                    .last_write_cycle
+                    .is_some_and(|last| self.cycle == last + 1);
+                self.last_write_cycle = Some(self.cycle);
+                if consecutive {
+                    return;
+                }
+
+                if get_u8_bit(val, 7) == 1 {
+                    self.shift = 0;
+                    self.shift_count = 0;
+                    self.control |= 0x0C;
+                    return;
+                }
+
+                self.shift |= (val & 0x01) << self.shift_count;
+                self.shift_count += 1;
+                if self.shift_count == 5 {
+                    self.write_register(addr, self.shift);
+                    self.shift = 0;
+                    self.shift_count = 0;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = val;
+        }
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match get_u8_bits(self.control, 1, 0) {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+use crate::util::*;
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+// CPU cycles A12 has to stay low before a rise counts, which filters out the
+// short drops between background and sprite fetches
+const A12_FILTER_CYCLES: u8 = 3;
+
+// Mapper 4: eight bank registers written through $8000/$8001, plus a scanline
+// counter clocked by rising edges of PPU address line A12.
+pub struct Mmc3 {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    prg_ram: Vec<u8>,
+    bank_select: u8,
+    banks: [u8; 8],
+    mirroring: Mirroring,
+    four_screen: bool,
+    prg_ram_enabled: bool,
+    prg_ram_protected: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12: bool,
+    a12_low_cycles: u8,
+}
+
+impl Mmc3 {
+    pub fn new(cartridge: Cartridge) -> Mmc3 {
+        Mmc3 {
+            chr: cartridge.chr_memory(),
+            chr_ram: cartridge.has_chr_ram(),
+            prg_ram: cartridge.prg_ram(),
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: cartridge.mirroring,
+            four_screen: cartridge.mirroring == Mirroring::FourScreen,
+            prg_ram_enabled: true,
+            prg_ram_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_cycles: 0,
+            prg: cartridge.prg_rom,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        // PRG smaller than a bank counts as one bank
+        let last_bank = (self.prg.len() / PRG_BANK_SIZE).max(1) - 1;
+        let second_last_bank = last_bank.saturating_sub(1);
+        let swap = get_u8_bit(self.bank_select, 6) == 1;
+        let bank = match (addr - 0x8000) / PRG_BANK_SIZE as u16 {
+            0 if swap => second_last_bank,
+            0 => self.banks[6] as usize,
+            1 => self.banks[7] as usize,
+            2 if swap => self.banks[6] as usize,
+            2 => second_last_bank,
+            _ => last_bank,
+        };
+        (bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1))) % self.prg.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        // CHR inversion swaps the 2K and 1K halves by flipping A12
+        let addr = if get_u8_bit(self.bank_select, 7) == 1 {
+            addr ^ 0x1000
+        } else {
+            addr
+        };
+        let bank = match addr / CHR_BANK_SIZE as u16 {
+            0 => self.banks[0] & 0xFE,
+            1 => self.banks[0] | 0x01,
+            2 => self.banks[1] & 0xFE,
+            3 => self.banks[1] | 0x01,
+            slot => self.banks[(slot - 2) as usize],
+        } as usize;
+        (bank * CHR_BANK_SIZE + (addr as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => {
+                Some(self.prg_ram[(addr - 0x6000) as usize % self.prg_ram.len()])
+            }
+            0x8000..=0xFFFF => Some(self.prg[self.prg_offset(addr)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        let even = addr & 0x01 == 0;
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled && !self.prg_ram_protected => {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr - 0x6000) as usize % len] = val;
+            }
+            0x8000..=0x9FFF if even => self.bank_select = val,
+            0x8000..=0x9FFF => self.banks[(self.bank_select & 0x07) as usize] = val,
+            // four-screen boards have their mirroring hardwired
+            0xA000..=0xBFFF if even && !self.four_screen => {
+                self.mirroring = if get_u8_bit(val, 0) == 1 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            0xA000..=0xBFFF if !even => {
+                self.prg_ram_enabled = get_u8_bit(val, 7) == 1;
+                self.prg_ram_protected = get_u8_bit(val, 6) == 1;
+            }
+            0xC000..=0xDFFF if even => self.irq_latch = val,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => (),
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = val;
+        }
+    }
+
+    fn ppu_address(&mut self, addr: u16) {
+        let a12 = get_u16_bit(addr, 12) == 1;
+        if a12 && !self.a12 && self.a12_low_cycles >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        }
+        if a12 {
+            self.a12_low_cycles = 0;
+        }
+        self.a12 = a12;
+    }
+
+    fn cpu_clock(&mut self) {
+        if !self.a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
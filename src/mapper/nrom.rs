@@ -0,0 +1,59 @@
+use crate::mapper::{Mapper, Mirroring};
+
+const PRG_RAM_SIZE: usize = 0x2000;
+const CHR_RAM_SIZE: usize = 0x2000;
+
+// Mapper 0: 16K or 32K of PRG mapped at $8000 (16K is mirrored at $C000)
+// and a fixed 8K of CHR.
+pub struct Nrom {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    prg_ram: [u8; PRG_RAM_SIZE],
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(prg: Vec<u8>, chr: Vec<u8>, mirroring: Mirroring) -> Nrom {
+        let chr_ram = chr.is_empty();
+        Nrom {
+            prg,
+            chr: if chr_ram { vec![0; CHR_RAM_SIZE] } else { chr },
+            chr_ram,
+            prg_ram: [0; PRG_RAM_SIZE],
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => Some(self.prg_ram[(addr - 0x6000) as usize]),
+            0x8000..=0xFFFF if !self.prg.is_empty() => {
+                Some(self.prg[(addr - 0x8000) as usize % self.prg.len()])
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram[(addr - 0x6000) as usize] = val;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr & 0x1FFF) as usize]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_ram {
+            self.chr[(addr & 0x1FFF) as usize] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,67 @@
+use crate::cartridge::Cartridge;
+use crate::mapper::{Mapper, Mirroring};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+// Mapper 2: switchable 16K PRG bank at $8000, last bank fixed at $C000,
+// 8K of CHR RAM.
+pub struct Uxrom {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    bank: u8,
+    bus_conflicts: bool,
+    mirroring: Mirroring,
+}
+
+impl Uxrom {
+    pub fn new(cartridge: Cartridge) -> Uxrom {
+        Uxrom {
+            chr: cartridge.chr_memory(),
+            chr_ram: cartridge.has_chr_ram(),
+            bank: 0,
+            // Submapper 1 marks boards without bus conflicts
+            bus_conflicts: cartridge.submapper != 1,
+            mirroring: cartridge.mirroring,
+            prg: cartridge.prg_rom,
+        }
+    }
+}
+
+impl Mapper for Uxrom {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        let bank = match addr {
+            0x8000..=0xBFFF => self.bank as usize,
+            // PRG smaller than a bank counts as one bank
+            0xC000..=0xFFFF => (self.prg.len() / PRG_BANK_SIZE).max(1) - 1,
+            _ => return None,
+        };
+        let offset = bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+        Some(self.prg[offset % self.prg.len()])
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        if addr >= 0x8000 {
+            let val = if self.bus_conflicts {
+                val & self.cpu_read(addr).unwrap_or(val)
+            } else {
+                val
+            };
+            self.bank = val;
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.chr[(addr & 0x1FFF) as usize]
+    }
+
+    fn ppu_write(&mut self, addr: u16, val: u8) {
+        if self.chr_ram {
+            self.chr[(addr & 0x1FFF) as usize] = val;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
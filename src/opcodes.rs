@@ -0,0 +1,345 @@
+use crate::cpu::Addressing::{self, *};
+use crate::cpu::Instructions::{self, *};
+use crate::util::*;
+
+// Everything the emulator knows about an opcode, in one place. The decoder,
+// the disassembler and the test harness all read from OPCODES.
+#[derive(Clone, Copy, Debug)]
+pub struct Opcode {
+    pub instr: Instructions,
+    pub addr: Addressing,
+    // cycles before page-cross and branch-taken penalties
+    pub cycles: u8,
+    // +1 cycle when indexing crosses a page (branches: +1 taken, +1 more on a cross)
+    pub page_cross: bool,
+    pub len: u8,
+    pub official: bool,
+}
+
+impl Addressing {
+    // Instruction length in bytes, opcode included
+    pub const fn size(&self) -> u8 {
+        match self {
+            IMP | ACC => 1,
+            IMM | ZPG | ZPX | ZPY | REL | IDX | IDY => 2,
+            ABS | ABX | ABY | IND => 3,
+        }
+    }
+}
+
+const fn op(
+    instr: Instructions,
+    addr: Addressing,
+    cycles: u8,
+    page_cross: bool,
+    official: bool,
+) -> Opcode {
+    Opcode {
+        instr,
+        addr,
+        cycles,
+        page_cross,
+        len: addr.size(),
+        official,
+    }
+}
+
+#[rustfmt::skip]
+pub static OPCODES: [Opcode; 256] = [
+    /* 00 */ op(BRK, IMP, 7, false, true),
+    /* 01 */ op(ORA, IDX, 6, false, true),
+    /* 02 */ op(KIL, IMP, 2, false, false),
+    /* 03 */ op(SLO, IDX, 8, false, false),
+    /* 04 */ op(NOP, ZPG, 3, false, false),
+    /* 05 */ op(ORA, ZPG, 3, false, true),
+    /* 06 */ op(ASL, ZPG, 5, false, true),
+    /* 07 */ op(SLO, ZPG, 5, false, false),
+    /* 08 */ op(PHP, IMP, 3, false, true),
+    /* 09 */ op(ORA, IMM, 2, false, true),
+    /* 0A */ op(ASL, ACC, 2, false, true),
+    /* 0B */ op(ANC, IMM, 2, false, false),
+    /* 0C */ op(NOP, ABS, 4, false, false),
+    /* 0D */ op(ORA, ABS, 4, false, true),
+    /* 0E */ op(ASL, ABS, 6, false, true),
+    /* 0F */ op(SLO, ABS, 6, false, false),
+    /* 10 */ op(BPL, REL, 2, true, true),
+    /* 11 */ op(ORA, IDY, 5, true, true),
+    /* 12 */ op(KIL, IMP, 2, false, false),
+    /* 13 */ op(SLO, IDY, 8, false, false),
+    /* 14 */ op(NOP, ZPX, 4, false, false),
+    /* 15 */ op(ORA, ZPX, 4, false, true),
+    /* 16 */ op(ASL, ZPX, 6, false, true),
+    /* 17 */ op(SLO, ZPX, 6, false, false),
+    /* 18 */ op(CLC, IMP, 2, false, true),
+    /* 19 */ op(ORA, ABY, 4, true, true),
+    /* 1A */ op(NOP, IMP, 2, false, false),
+    /* 1B */ op(SLO, ABY, 7, false, false),
+    /* 1C */ op(NOP, ABX, 4, true, false),
+    /* 1D */ op(ORA, ABX, 4, true, true),
+    /* 1E */ op(ASL, ABX, 7, false, true),
+    /* 1F */ op(SLO, ABX, 7, false, false),
+    /* 20 */ op(JSR, ABS, 6, false, true),
+    /* 21 */ op(AND, IDX, 6, false, true),
+    /* 22 */ op(KIL, IMP, 2, false, false),
+    /* 23 */ op(RLA, IDX, 8, false, false),
+    /* 24 */ op(BIT, ZPG, 3, false, true),
+    /* 25 */ op(AND, ZPG, 3, false, true),
+    /* 26 */ op(ROL, ZPG, 5, false, true),
+    /* 27 */ op(RLA, ZPG, 5, false, false),
+    /* 28 */ op(PLP, IMP, 4, false, true),
+    /* 29 */ op(AND, IMM, 2, false, true),
+    /* 2A */ op(ROL, ACC, 2, false, true),
+    /* 2B */ op(ANC, IMM, 2, false, false),
+    /* 2C */ op(BIT, ABS, 4, false, true),
+    /* 2D */ op(AND, ABS, 4, false, true),
+    /* 2E */ op(ROL, ABS, 6, false, true),
+    /* 2F */ op(RLA, ABS, 6, false, false),
+    /* 30 */ op(BMI, REL, 2, true, true),
+    /* 31 */ op(AND, IDY, 5, true, true),
+    /* 32 */ op(KIL, IMP, 2, false, false),
+    /* 33 */ op(RLA, IDY, 8, false, false),
+    /* 34 */ op(NOP, ZPX, 4, false, false),
+    /* 35 */ op(AND, ZPX, 4, false, true),
+    /* 36 */ op(ROL, ZPX, 6, false, true),
+    /* 37 */ op(RLA, ZPX, 6, false, false),
+    /* 38 */ op(SEC, IMP, 2, false, true),
+    /* 39 */ op(AND, ABY, 4, true, true),
+    /* 3A */ op(NOP, IMP, 2, false, false),
+    /* 3B */ op(RLA, ABY, 7, false, false),
+    /* 3C */ op(NOP, ABX, 4, true, false),
+    /* 3D */ op(AND, ABX, 4, true, true),
+    /* 3E */ op(ROL, ABX, 7, false, true),
+    /* 3F */ op(RLA, ABX, 7, false, false),
+    /* 40 */ op(RTI, IMP, 6, false, true),
+    /* 41 */ op(EOR, IDX, 6, false, true),
+    /* 42 */ op(KIL, IMP, 2, false, false),
+    /* 43 */ op(SRE, IDX, 8, false, false),
+    /* 44 */ op(NOP, ZPG, 3, false, false),
+    /* 45 */ op(EOR, ZPG, 3, false, true),
+    /* 46 */ op(LSR, ZPG, 5, false, true),
+    /* 47 */ op(SRE, ZPG, 5, false, false),
+    /* 48 */ op(PHA, IMP, 3, false, true),
+    /* 49 */ op(EOR, IMM, 2, false, true),
+    /* 4A */ op(LSR, ACC, 2, false, true),
+    /* 4B */ op(ALR, IMM, 2, false, false),
+    /* 4C */ op(JMP, ABS, 3, false, true),
+    /* 4D */ op(EOR, ABS, 4, false, true),
+    /* 4E */ op(LSR, ABS, 6, false, true),
+    /* 4F */ op(SRE, ABS, 6, false, false),
+    /* 50 */ op(BVC, REL, 2, true, true),
+    /* 51 */ op(EOR, IDY, 5, true, true),
+    /* 52 */ op(KIL, IMP, 2, false, false),
+    /* 53 */ op(SRE, IDY, 8, false, false),
+    /* 54 */ op(NOP, ZPX, 4, false, false),
+    /* 55 */ op(EOR, ZPX, 4, false, true),
+    /* 56 */ op(LSR, ZPX, 6, false, true),
+    /* 57 */ op(SRE, ZPX, 6, false, false),
+    /* 58 */ op(CLI, IMP, 2, false, true),
+    /* 59 */ op(EOR, ABY, 4, true, true),
+    /* 5A */ op(NOP, IMP, 2, false, false),
+    /* 5B */ op(SRE, ABY, 7, false, false),
+    /* 5C */ op(NOP, ABX, 4, true, false),
+    /* 5D */ op(EOR, ABX, 4, true, true),
+    /* 5E */ op(LSR, ABX, 7, false, true),
+    /* 5F */ op(SRE, ABX, 7, false, false),
+    /* 60 */ op(RTS, IMP, 6, false, true),
+    /* 61 */ op(ADC, IDX, 6, false, true),
+    /* 62 */ op(KIL, IMP, 2, false, false),
+    /* 63 */ op(RRA, IDX, 8, false, false),
+    /* 64 */ op(NOP, ZPG, 3, false, false),
+    /* 65 */ op(ADC, ZPG, 3, false, true),
+    /* 66 */ op(ROR, ZPG, 5, false, true),
+    /* 67 */ op(RRA, ZPG, 5, false, false),
+    /* 68 */ op(PLA, IMP, 4, false, true),
+    /* 69 */ op(ADC, IMM, 2, false, true),
+    /* 6A */ op(ROR, ACC, 2, false, true),
+    /* 6B */ op(ARR, IMM, 2, false, false),
+    /* 6C */ op(JMP, IND, 5, false, true),
+    /* 6D */ op(ADC, ABS, 4, false, true),
+    /* 6E */ op(ROR, ABS, 6, false, true),
+    /* 6F */ op(RRA, ABS, 6, false, false),
+    /* 70 */ op(BVS, REL, 2, true, true),
+    /* 71 */ op(ADC, IDY, 5, true, true),
+    /* 72 */ op(KIL, IMP, 2, false, false),
+    /* 73 */ op(RRA, IDY, 8, false, false),
+    /* 74 */ op(NOP, ZPX, 4, false, false),
+    /* 75 */ op(ADC, ZPX, 4, false, true),
+    /* 76 */ op(ROR, ZPX, 6, false, true),
+    /* 77 */ op(RRA, ZPX, 6, false, false),
+    /* 78 */ op(SEI, IMP, 2, false, true),
+    /* 79 */ op(ADC, ABY, 4, true, true),
+    /* 7A */ op(NOP, IMP, 2, false, false),
+    /* 7B */ op(RRA, ABY, 7, false, false),
+    /* 7C */ op(NOP, ABX, 4, true, false),
+    /* 7D */ op(ADC, ABX, 4, true, true),
+    /* 7E */ op(ROR, ABX, 7, false, true),
+    /* 7F */ op(RRA, ABX, 7, false, false),
+    /* 80 */ op(NOP, IMM, 2, false, false),
+    /* 81 */ op(STA, IDX, 6, false, true),
+    /* 82 */ op(NOP, IMM, 2, false, false),
+    /* 83 */ op(SAX, IDX, 6, false, false),
+    /* 84 */ op(STY, ZPG, 3, false, true),
+    /* 85 */ op(STA, ZPG, 3, false, true),
+    /* 86 */ op(STX, ZPG, 3, false, true),
+    /* 87 */ op(SAX, ZPG, 3, false, false),
+    /* 88 */ op(DEY, IMP, 2, false, true),
+    /* 89 */ op(NOP, IMM, 2, false, false),
+    /* 8A */ op(TXA, IMP, 2, false, true),
+    /* 8B */ op(XAA, IMM, 2, false, false),
+    /* 8C */ op(STY, ABS, 4, false, true),
+    /* 8D */ op(STA, ABS, 4, false, true),
+    /* 8E */ op(STX, ABS, 4, false, true),
+    /* 8F */ op(SAX, ABS, 4, false, false),
+    /* 90 */ op(BCC, REL, 2, true, true),
+    /* 91 */ op(STA, IDY, 6, false, true),
+    /* 92 */ op(KIL, IMP, 2, false, false),
+    /* 93 */ op(SHA, IDY, 6, false, false),
+    /* 94 */ op(STY, ZPX, 4, false, true),
+    /* 95 */ op(STA, ZPX, 4, false, true),
+    /* 96 */ op(STX, ZPY, 4, false, true),
+    /* 97 */ op(SAX, ZPY, 4, false, false),
+    /* 98 */ op(TYA, IMP, 2, false, true),
+    /* 99 */ op(STA, ABY, 5, false, true),
+    /* 9A */ op(TXS, IMP, 2, false, true),
+    /* 9B */ op(TAS, ABY, 5, false, false),
+    /* 9C */ op(SHY, ABX, 5, false, false),
+    /* 9D */ op(STA, ABX, 5, false, true),
+    /* 9E */ op(SHX, ABY, 5, false, false),
+    /* 9F */ op(SHA, ABY, 5, false, false),
+    /* A0 */ op(LDY, IMM, 2, false, true),
+    /* A1 */ op(LDA, IDX, 6, false, true),
+    /* A2 */ op(LDX, IMM, 2, false, true),
+    /* A3 */ op(LAX, IDX, 6, false, false),
+    /* A4 */ op(LDY, ZPG, 3, false, true),
+    /* A5 */ op(LDA, ZPG, 3, false, true),
+    /* A6 */ op(LDX, ZPG, 3, false, true),
+    /* A7 */ op(LAX, ZPG, 3, false, false),
+    /* A8 */ op(TAY, IMP, 2, false, true),
+    /* A9 */ op(LDA, IMM, 2, false, true),
+    /* AA */ op(TAX, IMP, 2, false, true),
+    /* AB */ op(LXA, IMM, 2, false, false),
+    /* AC */ op(LDY, ABS, 4, false, true),
+    /* AD */ op(LDA, ABS, 4, false, true),
+    /* AE */ op(LDX, ABS, 4, false, true),
+    /* AF */ op(LAX, ABS, 4, false, false),
+    /* B0 */ op(BCS, REL, 2, true, true),
+    /* B1 */ op(LDA, IDY, 5, true, true),
+    /* B2 */ op(KIL, IMP, 2, false, false),
+    /* B3 */ op(LAX, IDY, 5, true, false),
+    /* B4 */ op(LDY, ZPX, 4, false, true),
+    /* B5 */ op(LDA, ZPX, 4, false, true),
+    /* B6 */ op(LDX, ZPY, 4, false, true),
+    /* B7 */ op(LAX, ZPY, 4, false, false),
+    /* B8 */ op(CLV, IMP, 2, false, true),
+    /* B9 */ op(LDA, ABY, 4, true, true),
+    /* BA */ op(TSX, IMP, 2, false, true),
+    /* BB */ op(LAS, ABY, 4, true, false),
+    /* BC */ op(LDY, ABX, 4, true, true),
+    /* BD */ op(LDA, ABX, 4, true, true),
+    /* BE */ op(LDX, ABY, 4, true, true),
+    /* BF */ op(LAX, ABY, 4, true, false),
+    /* C0 */ op(CPY, IMM, 2, false, true),
+    /* C1 */ op(CMP, IDX, 6, false, true),
+    /* C2 */ op(NOP, IMM, 2, false, false),
+    /* C3 */ op(DCP, IDX, 8, false, false),
+    /* C4 */ op(CPY, ZPG, 3, false, true),
+    /* C5 */ op(CMP, ZPG, 3, false, true),
+    /* C6 */ op(DEC, ZPG, 5, false, true),
+    /* C7 */ op(DCP, ZPG, 5, false, false),
+    /* C8 */ op(INY, IMP, 2, false, true),
+    /* C9 */ op(CMP, IMM, 2, false, true),
+    /* CA */ op(DEX, IMP, 2, false, true),
+    /* CB */ op(AXS, IMM, 2, false, false),
+    /* CC */ op(CPY, ABS, 4, false, true),
+    /* CD */ op(CMP, ABS, 4, false, true),
+    /* CE */ op(DEC, ABS, 6, false, true),
+    /* CF */ op(DCP, ABS, 6, false, false),
+    /* D0 */ op(BNE, REL, 2, true, true),
+    /* D1 */ op(CMP, IDY, 5, true, true),
+    /* D2 */ op(KIL, IMP, 2, false, false),
+    /* D3 */ op(DCP, IDY, 8, false, false),
+    /* D4 */ op(NOP, ZPX, 4, false, false),
+    /* D5 */ op(CMP, ZPX, 4, false, true),
+    /* D6 */ op(DEC, ZPX, 6, false, true),
+    /* D7 */ op(DCP, ZPX, 6, false, false),
+    /* D8 */ op(CLD, IMP, 2, false, true),
+    /* D9 */ op(CMP, ABY, 4, true, true),
+    /* DA */ op(NOP, IMP, 2, false, false),
+    /* DB */ op(DCP, ABY, 7, false, false),
+    /* DC */ op(NOP, ABX, 4, true, false),
+    /* DD */ op(CMP, ABX, 4, true, true),
+    /* DE */ op(DEC, ABX, 7, false, true),
+    /* DF */ op(DCP, ABX, 7, false, false),
+    /* E0 */ op(CPX, IMM, 2, false, true),
+    /* E1 */ op(SBC, IDX, 6, false, true),
+    /* E2 */ op(NOP, IMM, 2, false, false),
+    /* E3 */ op(ISC, IDX, 8, false, false),
+    /* E4 */ op(CPX, ZPG, 3, false, true),
+    /* E5 */ op(SBC, ZPG, 3, false, true),
+    /* E6 */ op(INC, ZPG, 5, false, true),
+    /* E7 */ op(ISC, ZPG, 5, false, false),
+    /* E8 */ op(INX, IMP, 2, false, true),
+    /* E9 */ op(SBC, IMM, 2, false, true),
+    /* EA */ op(NOP, IMP, 2, false, true),
+    /* EB */ op(SBC, IMM, 2, false, false),
+    /* EC */ op(CPX, ABS, 4, false, true),
+    /* ED */ op(SBC, ABS, 4, false, true),
+    /* EE */ op(INC, ABS, 6, false, true),
+    /* EF */ op(ISC, ABS, 6, false, false),
+    /* F0 */ op(BEQ, REL, 2, true, true),
+    /* F1 */ op(SBC, IDY, 5, true, true),
+    /* F2 */ op(KIL, IMP, 2, false, false),
+    /* F3 */ op(ISC, IDY, 8, false, false),
+    /* F4 */ op(NOP, ZPX, 4, false, false),
+    /* F5 */ op(SBC, ZPX, 4, false, true),
+    /* F6 */ op(INC, ZPX, 6, false, true),
+    /* F7 */ op(ISC, ZPX, 6, false, false),
+    /* F8 */ op(SED, IMP, 2, false, true),
+    /* F9 */ op(SBC, ABY, 4, true, true),
+    /* FA */ op(NOP, IMP, 2, false, false),
+    /* FB */ op(ISC, ABY, 7, false, false),
+    /* FC */ op(NOP, ABX, 4, true, false),
+    /* FD */ op(SBC, ABX, 4, true, true),
+    /* FE */ op(INC, ABX, 7, false, true),
+    /* FF */ op(ISC, ABX, 7, false, false),
+];
+
+// Nintendulator names ISC "ISB"; everything else matches our mnemonics
+pub fn mnemonic(instr: Instructions) -> String {
+    match instr {
+        ISC => String::from("ISB"),
+        _ => format!("{:?}", instr),
+    }
+}
+
+// Disassemble the instruction starting at bytes[0], which lives at `pc`
+// (needed to resolve relative branch targets). Unofficial opcodes are
+// prefixed with '*' like in Nintendulator traces.
+pub fn disassemble(bytes: &[u8], pc: u16) -> String {
+    let opcode = &OPCODES[bytes[0] as usize];
+    let low = bytes.get(1).copied().unwrap_or(0);
+    let high = bytes.get(2).copied().unwrap_or(0);
+    let word = combine_low_high(low, high);
+    let operand = match opcode.addr {
+        IMP => String::new(),
+        ACC => String::from("A"),
+        IMM => format!("#${:02X}", low),
+        ZPG => format!("${:02X}", low),
+        ZPX => format!("${:02X},X", low),
+        ZPY => format!("${:02X},Y", low),
+        REL => format!(
+            "${:04X}",
+            pc.wrapping_add(2).wrapping_add_signed(low as i8 as i16)
+        ),
+        ABS => format!("${:04X}", word),
+        ABX => format!("${:04X},X", word),
+        ABY => format!("${:04X},Y", word),
+        IND => format!("(${:04X})", word),
+        IDX => format!("(${:02X},X)", low),
+        IDY => format!("(${:02X}),Y", low),
+    };
+    let prefix = if opcode.official { "" } else { "*" };
+    format!("{}{} {}", prefix, mnemonic(opcode.instr), operand)
+        .trim_end()
+        .to_string()
+}
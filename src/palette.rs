@@ -0,0 +1,101 @@
+use std::fmt;
+
+// A .pal file is 64 RGB triples, or 512 when it also covers the 8 color
+// emphasis combinations
+const COLORS: usize = 64;
+const EMPHASIS_COLORS: usize = COLORS * 8;
+// How much each emphasis bit dims the channels it doesn't name, when building
+// the emphasis entries for a 64 color palette
+const ATTENUATION: f32 = 0.816328;
+
+// The NTSC 2C02 palette the emulator uses unless told otherwise
+const DEFAULT_PALETTE: &[u8; COLORS * 3] = include_bytes!("palette.pal");
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(std::io::Error),
+    BadSize(usize),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(why) => write!(f, "could not read palette: {}", why),
+            PaletteError::BadSize(len) => write!(
+                f,
+                "palette is {} bytes, expected {} or {}",
+                len,
+                COLORS * 3,
+                EMPHASIS_COLORS * 3
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl From<std::io::Error> for PaletteError {
+    fn from(why: std::io::Error) -> PaletteError {
+        PaletteError::Io(why)
+    }
+}
+
+pub struct Palette {
+    // indexed by emphasis << 6 | color
+    colors: Vec<(u8, u8, u8)>,
+}
+
+impl Palette {
+    pub fn ntsc() -> Palette {
+        Palette::from_bytes(DEFAULT_PALETTE).unwrap()
+    }
+
+    pub fn from_bytes(pal: &[u8]) -> Result<Palette, PaletteError> {
+        if pal.len() != COLORS * 3 && pal.len() != EMPHASIS_COLORS * 3 {
+            return Err(PaletteError::BadSize(pal.len()));
+        }
+        let mut colors: Vec<(u8, u8, u8)> = pal.chunks(3).map(|c| (c[0], c[1], c[2])).collect();
+        if colors.len() == COLORS {
+            for emphasis in 1..8 {
+                for color in 0..COLORS {
+                    colors.push(emphasize(colors[color], color, emphasis));
+                }
+            }
+        }
+        Ok(Palette { colors })
+    }
+
+    pub fn from_path(path: &str) -> Result<Palette, PaletteError> {
+        Palette::from_bytes(&std::fs::read(path)?)
+    }
+
+    // RGB for a 6 bit NES color, with the PPUMASK emphasis bits (red, green,
+    // blue from bit 6 up)
+    pub fn rgb(&self, color: u16) -> (u8, u8, u8) {
+        self.colors[color as usize % EMPHASIS_COLORS]
+    }
+
+    // A whole framebuffer as packed RGB24
+    pub fn to_rgb24(&self, framebuffer: &[u16], pixels: &mut [u8]) {
+        for (n, color) in framebuffer.iter().enumerate() {
+            let (r, g, b) = self.rgb(*color);
+            pixels[n * 3] = r;
+            pixels[n * 3 + 1] = g;
+            pixels[n * 3 + 2] = b;
+        }
+    }
+}
+
+// Each emphasis bit dims the other two channels, so a channel is dimmed once
+// for every set bit but its own and all three darken the whole color. The
+// blacks in columns $E and $F have no signal to dim.
+fn emphasize(rgb: (u8, u8, u8), color: usize, emphasis: usize) -> (u8, u8, u8) {
+    if color & 0x0F >= 0x0E {
+        return rgb;
+    }
+    let dim = |channel: u8, bit: usize| {
+        let others = (emphasis & !(1 << bit)).count_ones() as i32;
+        (channel as f32 * ATTENUATION.powi(others)) as u8
+    };
+    (dim(rgb.0, 0), dim(rgb.1, 1), dim(rgb.2, 2))
+}
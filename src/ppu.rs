@@ -1,24 +1,20 @@
 use crate::util::*;
 use crate::Bus;
-use crate::Cpu;
-
-use colors_transform::{Color as ColorT, Hsl, Rgb};
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
 
 pub const PALETTE_ADDRESS: u16 = 0x3F00;
+pub const SPRITE_PALETTE_ADDRESS: u16 = 0x3F10;
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 240;
+// Palette index the framebuffer starts out as
+const BLACK: u16 = 0x0F;
 
 pub struct Status {
-    vblank: bool,
-    hit: bool,
-    overflow: bool,
-    bus: u8,
+    pub vblank: bool,
+    pub hit: bool,
+    pub overflow: bool,
 }
 
 pub struct Control {
-    nametable_address: u16,
     vram_increment: u8,
     sprite_address: u8,
     background_address: u8,
@@ -38,16 +34,6 @@ pub struct Mask {
     blue: bool,
 }
 
-pub fn parse_nametable(nametable_bits: u8) -> u16 {
-    match nametable_bits {
-        0 => 0x2000,
-        1 => 0x2400,
-        2 => 0x2800,
-        3 => 0x2C00,
-        _ => panic!(),
-    }
-}
-
 impl Mask {
     pub fn new() -> Mask {
         Mask {
@@ -63,7 +49,7 @@ impl Mask {
     }
 
     pub fn read(&mut self, bus: &mut Bus) {
-        let byte = bus.cpu_read_16(CONTROL);
+        let byte = bus.cpu_read_16(MASK);
         self.greyscale = get_u8_bit(byte, 0) == 1;
         self.background_left_8 = get_u8_bit(byte, 1) == 1;
         self.sprite_left_8 = get_u8_bit(byte, 2) == 1;
@@ -73,12 +59,30 @@ impl Mask {
         self.green = get_u8_bit(byte, 6) == 1;
         self.blue = get_u8_bit(byte, 7) == 1;
     }
+
+    // Emphasis bits in the framebuffer's layout, ready to OR above a color
+    pub fn emphasis(&self) -> u16 {
+        ((self.red as u16) | (self.green as u16) << 1 | (self.blue as u16) << 2) << 6
+    }
+
+    // Greyscale keeps only the brightness column of the palette
+    pub fn apply_greyscale(&self, color: u8) -> u8 {
+        if self.greyscale {
+            color & 0x30
+        } else {
+            color & 0x3F
+        }
+    }
+
+    // Whether a layer is shown at this x, given its enable and left 8 bits
+    pub fn shows(&self, x: u16, enabled: bool, left_8: bool) -> bool {
+        enabled && (x >= 8 || left_8)
+    }
 }
 
 impl Control {
     pub fn new() -> Control {
         Control {
-            nametable_address: parse_nametable(0),
             vram_increment: 1,
             sprite_address: 0,
             background_address: 0,
@@ -97,7 +101,6 @@ impl Control {
         self.sprite_address = get_u8_bit(byte, 3);
         let vram_increment = get_u8_bit(byte, 2);
         self.vram_increment = if vram_increment == 0 { 1 } else { 32 };
-        self.nametable_address = parse_nametable(get_u8_bits(byte, 1, 0));
     }
 }
 
@@ -107,16 +110,16 @@ impl Status {
             vblank: false,
             hit: false,
             overflow: false,
-            bus: 0,
         }
     }
 
+    // The three flags in bits 5-7, the only ones $2002 drives
+    pub fn byte(&self) -> u8 {
+        (self.vblank as u8) << 7 | (self.hit as u8) << 6 | (self.overflow as u8) << 5
+    }
+
     pub fn write(&mut self, bus: &mut Bus) {
-        let byte = (self.vblank as u8) << 7
-            | (self.hit as u8) << 6
-            | (self.overflow as u8) << 5
-            | self.bus;
-        bus.cpu_write_16(STATUS, byte);
+        bus.cpu_write_16(STATUS, self.byte());
     }
 }
 
@@ -124,18 +127,44 @@ pub struct Ppu {
     oam: [u8; 256],
     pub cycle: u16,
     pub line: u16,
-    nametable_addr: u16,
     pub status: Status,
     pub control: Control,
     pub mask: Mask,
-    pub addr: u16,
-    pub oam_addr: u16,
-    sprites: [u8; 8],
-    sprite_index: usize,
-    v: u8,
-    t: u8,
-    x: u8,
-    w: bool,
+    pub oam_addr: u8,
+    // the I/O latch: the last value on the data bus between the CPU and the
+    // PPU registers. Write-only registers read back as it, and so do the
+    // bits of $2002 the flags don't cover
+    pub io_latch: u8,
+    // set by a $2002 read the dot before vblank starts, which keeps the flag
+    // from being set that frame
+    skip_vblank: bool,
+    // sprites found for the next line, and the 8 sprite slots (pattern
+    // shifters, attribute latches and x counters) loaded from them
+    secondary_oam: [u8; 32],
+    sprite_count: usize,
+    sprite_zero_in_range: bool,
+    sprite_patterns: [(u8, u8); 8],
+    sprite_attributes: [u8; 8],
+    sprite_x: [u8; 8],
+    // loopy registers: current and temporary vram address, fine x scroll and
+    // the write toggle shared by $2005 and $2006
+    pub v: u16,
+    pub t: u16,
+    pub x: u8,
+    pub w: bool,
+    // $2007 reads return the byte fetched by the previous read
+    read_buffer: u8,
+    // background tile being fetched, and the shift registers feeding pixels
+    next_tile: u8,
+    next_attribute: u8,
+    next_pattern: (u8, u8),
+    pattern_shifters: (u16, u16),
+    attribute_shifters: (u16, u16),
+    // one NES color (palette index) per pixel, row by row, with the PPUMASK
+    // emphasis bits above it (red, green, blue from bit 6 up)
+    pub framebuffer: Vec<u16>,
+    // frames finished since power on
+    pub frame: u64,
 }
 
 impl Ppu {
@@ -144,44 +173,273 @@ impl Ppu {
             oam: [0; 256],
             cycle: 0,
             line: 0,
-            nametable_addr: 0,
             status: Status::new(),
             control: Control::new(),
             mask: Mask::new(),
-            addr: 0,
             oam_addr: 0,
-            sprites: [0; 8],
-            sprite_index: 0,
+            io_latch: 0,
+            skip_vblank: false,
+            secondary_oam: [0xFF; 32],
+            sprite_count: 0,
+            sprite_zero_in_range: false,
+            sprite_patterns: [(0, 0); 8],
+            sprite_attributes: [0; 8],
+            sprite_x: [0; 8],
             v: 0,
             t: 0,
             x: 0,
             w: false,
+            read_buffer: 0,
+            next_tile: 0,
+            next_attribute: 0,
+            next_pattern: (0, 0),
+            pattern_shifters: (0, 0),
+            attribute_shifters: (0, 0),
+            framebuffer: vec![BLACK; WIDTH * HEIGHT],
+            frame: 0,
+        }
+    }
+
+    // The reset button clears PPUCTRL, PPUMASK and the write toggle; the rest
+    // of the PPU keeps running
+    pub fn reset(&mut self) {
+        self.control = Control::new();
+        self.mask = Mask::new();
+        self.w = false;
+    }
+
+    // Reading $2002 clears vblank and the write toggle. A read the dot before
+    // vblank starts sees it clear and the flag never gets set that frame; one
+    // in the same cpu cycle as the set sees it, but clears it before the cpu
+    // polls /NMI, so there's no NMI either
+    pub fn read_status(&mut self, bus: &mut Bus) -> u8 {
+        let byte = self.status.byte() | self.io_latch & 0x1F;
+        if self.line == 241 && self.cycle == 1 {
+            self.skip_vblank = true;
         }
+        self.status.vblank = false;
+        self.status.write(bus);
+        bus.nmi = false;
+        self.w = false;
+        self.io_latch = byte;
+        byte
+    }
+
+    // VRAM reads go through a buffer and come out one read late. Palette
+    // reads come out straight away, but still fill the buffer with the
+    // nametable byte underneath
+    pub fn read_data(&mut self, bus: &mut Bus) -> u8 {
+        let addr = self.v & 0x3FFF;
+        let data = if addr >= PALETTE_ADDRESS {
+            self.read_buffer = bus.ppu_read_16(addr - 0x1000);
+            bus.ppu_read_16(addr)
+        } else {
+            let data = self.read_buffer;
+            self.read_buffer = bus.ppu_read_16(addr);
+            data
+        };
+        self.v = (self.v + self.control.vram_increment as u16) & 0x7FFF;
+        self.io_latch = data;
+        data
     }
 
     pub fn write_data(&mut self, data: u8, bus: &mut Bus) {
-        bus.ppu_write_16(self.addr, data);
-        self.addr += self.control.vram_increment as u16;
-        //println!("ppu write {:0x} {:0x}", self.addr, data);
+        bus.ppu_write_16(self.v & 0x3FFF, data);
+        self.v = (self.v + self.control.vram_increment as u16) & 0x7FFF;
+        //println!("ppu write {:0x} {:0x}", self.v, data);
+    }
+
+    // $2000 also picks the base nametable through t
+    pub fn write_control(&mut self, bus: &mut Bus) {
+        self.control.read(bus);
+        let byte = bus.cpu_read_16(CONTROL);
+        self.t = (self.t & !0x0C00) | (get_u8_bits(byte, 1, 0) as u16) << 10;
+    }
+
+    // First write: coarse and fine X, second write: coarse and fine Y
+    pub fn write_scroll(&mut self, val: u8) {
+        if !self.w {
+            self.t = (self.t & !0x001F) | (val >> 3) as u16;
+            self.x = val & 0x07;
+        } else {
+            self.t = (self.t & !0x73E0) | ((val & 0x07) as u16) << 12 | ((val >> 3) as u16) << 5;
+        }
+        self.w = !self.w;
     }
 
+    // First write: high 6 bits of t, second write: low byte, then t goes to v
     pub fn write_addr(&mut self, addr: u8) {
         if !self.w {
-            self.addr &= 0x00FF;
-            self.addr |= (addr as u16) << 8;
+            self.t = (self.t & 0x00FF) | ((addr & 0x3F) as u16) << 8;
         } else {
-            self.addr &= 0xFF00;
-            self.addr |= addr as u16;
+            self.t = (self.t & 0xFF00) | addr as u16;
+            self.v = self.t;
         }
         self.w = !self.w;
     }
 
-    pub fn write_oam(&mut self, addr: u8, bus: &mut Bus) {
-        let mut new_addr = (addr as u16) << 8;
-        for n in 0..0xFF {
-            self.oam[n] = bus.cpu_read_16(new_addr);
-            new_addr += 1;
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask.background || self.mask.sprite
+    }
+
+    // Whether the PPU is busy fetching: rendering on, on a visible or the
+    // pre-render line
+    pub fn rendering(&self) -> bool {
+        self.rendering_enabled() && (self.line < 240 || self.line == 261)
+    }
+
+    // --------------------- LOOPY v -----------------------
+
+    // Coarse X wraps into the horizontally adjacent nametable
+    fn increment_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    // Fine Y carries into coarse Y, which wraps into the vertically adjacent
+    // nametable after row 29 (rows 30 and 31 are attributes, and wrap in place)
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | coarse_y << 5;
+    }
+
+    fn copy_horizontal(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    fn copy_vertical(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+
+    // --------------------- BACKGROUND -----------------------
+
+    // The fetched tile goes into the low byte of the shifters, the attribute
+    // bits are stretched to cover all 8 of its pixels
+    fn load_background_shifters(&mut self) {
+        self.pattern_shifters.0 = (self.pattern_shifters.0 & 0xFF00) | self.next_pattern.0 as u16;
+        self.pattern_shifters.1 = (self.pattern_shifters.1 & 0xFF00) | self.next_pattern.1 as u16;
+        let low = if get_u8_bit(self.next_attribute, 0) == 1 {
+            0xFF
+        } else {
+            0x00
+        };
+        let high = if get_u8_bit(self.next_attribute, 1) == 1 {
+            0xFF
+        } else {
+            0x00
+        };
+        self.attribute_shifters.0 = (self.attribute_shifters.0 & 0xFF00) | low;
+        self.attribute_shifters.1 = (self.attribute_shifters.1 & 0xFF00) | high;
+    }
+
+    fn shift_background(&mut self) {
+        self.pattern_shifters.0 <<= 1;
+        self.pattern_shifters.1 <<= 1;
+        self.attribute_shifters.0 <<= 1;
+        self.attribute_shifters.1 <<= 1;
+    }
+
+    // Nametable, attribute and the two pattern bytes for the next tile, one
+    // fetch every other dot, then on to the next tile
+    fn fetch_background(&mut self, bus: &mut Bus) {
+        match (self.cycle - 1) % 8 {
+            0 => {
+                self.load_background_shifters();
+                self.next_tile = bus.ppu_read_16(0x2000 | (self.v & 0x0FFF));
+            }
+            2 => {
+                let attribute = bus.ppu_read_16(
+                    0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07),
+                );
+                // each byte covers 4x4 tiles, two bits per 2x2 quadrant
+                let shift = ((self.v >> 4) & 0x04) | (self.v & 0x02);
+                self.next_attribute = (attribute >> shift) & 0x03;
+            }
+            4 => {
+                let fine_y = (self.v >> 12) & 0x07;
+                let tile = self.next_tile;
+                let half = self.control.background_address;
+                self.next_pattern = self.get_pattern_address(bus, tile, fine_y, half);
+            }
+            7 => self.increment_x(),
+            _ => (),
+        }
+    }
+
+    fn background_tick(&mut self, bus: &mut Bus) {
+        if (self.cycle >= 2 && self.cycle <= 257) || (self.cycle >= 322 && self.cycle <= 337) {
+            self.shift_background();
+        }
+        if (self.cycle >= 1 && self.cycle <= 256) || (self.cycle >= 321 && self.cycle <= 336) {
+            self.fetch_background(bus);
+        }
+        if self.cycle == 256 {
+            self.increment_y();
+        } else if self.cycle == 257 {
+            self.load_background_shifters();
+            self.copy_horizontal();
+        } else if self.line == 261 && self.cycle >= 280 && self.cycle <= 304 {
+            self.copy_vertical();
+        }
+    }
+
+    // 2 bit pixel and palette number under the current dot, fine x picks the bit
+    fn background_pixel(&self) -> (u8, u8) {
+        let bit = 15 - self.x;
+        let pixel = ((self.pattern_shifters.1 >> bit) as u8 & 1) << 1
+            | ((self.pattern_shifters.0 >> bit) as u8 & 1);
+        let palette = ((self.attribute_shifters.1 >> bit) as u8 & 1) << 1
+            | ((self.attribute_shifters.0 >> bit) as u8 & 1);
+        (pixel, palette)
+    }
+
+    // OAM DMA: the page at $XX00 goes to OAM starting at OAMADDR, wrapping
+    // around, so OAMADDR ends up where it started
+    pub fn write_oam(&mut self, page: u8, bus: &mut Bus) {
+        let page = (page as u16) << 8;
+        for n in 0..=0xFF {
+            let val = bus.cpu_read_16(page | n);
+            self.oam[self.oam_addr.wrapping_add(n as u8) as usize] = val;
+        }
+    }
+
+    // While rendering, $2004 writes don't reach OAM but glitchily bump the
+    // high 6 bits of OAMADDR
+    pub fn write_oam_data(&mut self, val: u8) {
+        if self.rendering() {
+            self.oam_addr = self.oam_addr.wrapping_add(4);
+        } else {
+            self.oam[self.oam_addr as usize] = val;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    // Reading $2004 doesn't move OAMADDR. Bits 2-4 of the attribute byte
+    // don't exist and read back as 0
+    pub fn read_oam_data(&mut self) -> u8 {
+        let mut val = self.oam[self.oam_addr as usize];
+        if self.oam_addr & 0x03 == 2 {
+            val &= 0xE3;
         }
+        self.io_latch = val;
+        val
     }
 
     pub fn get_pattern_address(
@@ -199,211 +457,222 @@ impl Ppu {
         (pattern_byte_0, pattern_byte_1)
     }
 
-    pub fn draw_tile(
-        &mut self,
-        bus: &mut Bus,
-        canvas: &mut Canvas<Window>,
-        pattern_byte_0: u8,
-        pattern_byte_1: u8,
-        n: u8,
-        palette_color: u16,
-        use_offset: bool,
-    ) -> bool {
-        let bit_0 = get_u8_bit(pattern_byte_0, 7 - n);
-        let bit_1 = get_u8_bit(pattern_byte_1, 7 - n);
-        let sum = bit_1 << 1 | bit_0;
-        if sum > 0 {
-            let color = bus.ppu_read_16(palette_color + sum as u16);
-            let hue = ((color & 0x0F) as f32 / 0x100 as f32) * 360.0;
-            let brightness = ((color >> 4 & 0b00000011) as f32 / 0b100 as f32) * 100.0;
-            let rgb_color = Hsl::from(hue, 100.0, brightness).to_rgb();
-            canvas.set_draw_color(Color::RGB(
-                rgb_color.get_red() as u8,
-                rgb_color.get_green() as u8,
-                rgb_color.get_blue() as u8,
-            ));
-
-            let point = Point::new(
-                (self.cycle + if use_offset { n } else { 0 } as u16) as i32,
-                self.line as i32,
-            );
-            if self.mask.background {
-                //canvas.draw_point(point);
-            }
-            canvas.draw_point(point);
-            return true;
+    // --------------------- SPRITES -----------------------
+
+    pub fn sprite_height(&self) -> u16 {
+        if self.control.sprite_size == 1 {
+            16
+        } else {
+            8
         }
-        false
     }
 
-    pub fn tick(&mut self, bus: &mut Bus, canvas: &mut Canvas<Window>, cpu: &mut Cpu) -> u8 {
-        let mut cycles = 0;
-        let mut sprite_0_draw = false;
-        let mut background_draw = false;
-        if self.line < 240 {
-            // --------------------- GET SPRITES FOR NEXT LINE -----------------------
-            if self.cycle == 257 {
-                while self.sprite_index > 0
-                    && self.line >= 8
-                    && self.oam[(self.sprites[self.sprite_index - 1] * 4) as usize]
-                        < (self.line - 8) as u8
-                {
-                    self.sprite_index -= 1;
+    // Whether a sprite at OAM y shows up on the line after this one
+    fn sprite_in_range(&self, y: u8) -> bool {
+        self.line.wrapping_sub(y as u16) < self.sprite_height()
+    }
+
+    // Copy the first 8 sprites in range of the next line into secondary OAM.
+    // Past 8, the PPU keeps looking for a 9th to set the overflow flag, but
+    // increments the byte index along with the sprite index, so it compares
+    // tile numbers, attributes and x positions as if they were y.
+    fn evaluate_sprites(&mut self, bus: &mut Bus) {
+        self.secondary_oam = [0xFF; 32];
+        self.sprite_count = 0;
+        self.sprite_zero_in_range = false;
+        let mut n = 0;
+        let mut m = 0;
+        while n < 64 {
+            if self.sprite_count < 8 {
+                let y = self.oam[n * 4];
+                if self.sprite_in_range(y) {
+                    let slot = self.sprite_count * 4;
+                    self.secondary_oam[slot..slot + 4].copy_from_slice(&self.oam[n * 4..n * 4 + 4]);
+                    self.sprite_zero_in_range |= n == 0;
+                    self.sprite_count += 1;
                 }
-                for n in 0..(self.oam.len() / 4) {
-                    if self.line + 1 == self.oam[n * 4] as u16 {
-                        if self.sprite_index == 8 {
-                            self.status.overflow = true;
-                            self.status.write(bus);
-                        } else {
-                            self.sprites[self.sprite_index] = n as u8;
-                            self.sprite_index += 1;
-                        }
-                    }
+                n += 1;
+            } else {
+                if self.sprite_in_range(self.oam[n * 4 + m]) {
+                    self.status.overflow = true;
+                    self.status.write(bus);
+                    break;
                 }
+                n += 1;
+                m = (m + 1) % 4;
             }
-            if self.cycle >= 1 && self.cycle <= 256 {
-                let n = (self.cycle) % 8;
-                // --------------------- NAMETABLE -----------------------
-                let nametable_address = self.control.nametable_address
-                    + (((self.line + 1) / 8) * 32 + (self.cycle / 8));
-                let nametable_byte = bus.ppu_read_16(nametable_address);
-
-                let (pattern_byte_0, pattern_byte_1) = self.get_pattern_address(
-                    bus,
-                    nametable_byte,
-                    (self.line + 1) % 8,
-                    self.control.background_address,
-                );
+        }
+    }
 
-                //println!("nametable_byte {:0x}", nametable_byte);
-                //println!("pattern_address {:0x}", pattern_address_0);
+    // Sprite patterns are fetched during dots 257-320 like the real PPU, so the
+    // cartridge sees A12 rise once per line even when no sprites are in range.
+    // Flips are applied here so the shifters always shift out the left pixel.
+    fn fetch_sprite(&mut self, bus: &mut Bus, j: usize) {
+        let height = self.sprite_height();
+        if j >= self.sprite_count {
+            // Empty slots still fetch tile $FF
+            if height == 16 {
+                self.get_pattern_address(bus, 0xFE, 0, 1);
+            } else {
+                self.get_pattern_address(bus, 0xFF, 0, self.control.sprite_address);
+            }
+            self.sprite_patterns[j] = (0, 0);
+            self.sprite_attributes[j] = 0;
+            self.sprite_x[j] = 0xFF;
+            return;
+        }
 
-                // --------------------- ATTRIBUTE TABLE -----------------------
+        let y = self.secondary_oam[j * 4];
+        let mut tile = self.secondary_oam[j * 4 + 1];
+        let attr = self.secondary_oam[j * 4 + 2];
+        let mut row = self.line.wrapping_sub(y as u16) % height;
+        if get_u8_bit(attr, 7) == 1 {
+            row = height - 1 - row;
+        }
+        let table_half = if height == 16 {
+            // 8x16 sprites pick their table with bit 0 and use a pair of tiles
+            let half = get_u8_bit(tile, 0);
+            tile &= 0xFE;
+            if row >= 8 {
+                tile += 1;
+                row -= 8;
+            }
+            half
+        } else {
+            self.control.sprite_address
+        };
+        let (mut pattern_byte_0, mut pattern_byte_1) =
+            self.get_pattern_address(bus, tile, row, table_half);
+        if get_u8_bit(attr, 6) == 1 {
+            pattern_byte_0 = pattern_byte_0.reverse_bits();
+            pattern_byte_1 = pattern_byte_1.reverse_bits();
+        }
+        self.sprite_patterns[j] = (pattern_byte_0, pattern_byte_1);
+        self.sprite_attributes[j] = attr;
+        self.sprite_x[j] = self.secondary_oam[j * 4 + 3];
+    }
 
-                let attribute = bus.ppu_read_16(
-                    (self.control.nametable_address + 0x3C0)
-                        + ((240 - self.line) / 32) * 8
-                        + (256 - self.cycle) / 32,
+    // First opaque pixel among the sprites whose x counter has run out, in
+    // OAM order: (pixel, palette, behind background, is sprite 0)
+    fn sprite_pixel(&self) -> (u8, u8, bool, bool) {
+        for j in 0..self.sprite_count {
+            if self.sprite_x[j] > 0 {
+                continue;
+            }
+            let (pattern_byte_0, pattern_byte_1) = self.sprite_patterns[j];
+            let pixel = get_u8_bit(pattern_byte_1, 7) << 1 | get_u8_bit(pattern_byte_0, 7);
+            if pixel > 0 {
+                let attr = self.sprite_attributes[j];
+                return (
+                    pixel,
+                    attr & 0b00000011,
+                    get_u8_bit(attr, 5) == 1,
+                    j == 0 && self.sprite_zero_in_range,
                 );
+            }
+        }
+        (0, 0, false, false)
+    }
 
-                let top_left = attribute & 0b00000011;
-                let top_right = attribute >> 2 & 0b00000011;
-                let bottom_left = attribute >> 4 & 0b00000011;
-                let bottom_right = attribute >> 6 & 0b00000011;
-
-                let rel_x = self.cycle % 16;
-                let rel_y = self.line % 16;
-                let mut palette_offset = 0;
-
-                if rel_x <= 7 {
-                    if rel_y <= 7 {
-                        palette_offset = top_left * 4;
-                    } else if rel_y > 7 {
-                        palette_offset = bottom_left * 4;
-                    }
-                } else if rel_x > 7 {
-                    if rel_y <= 7 {
-                        palette_offset = top_right * 4;
-                    } else if rel_y > 7 {
-                        palette_offset = bottom_right * 4;
-                    }
-                }
+    // Count x down to the sprite, then shift its pattern out a pixel per dot
+    fn shift_sprites(&mut self) {
+        for j in 0..self.sprite_count {
+            if self.sprite_x[j] > 0 {
+                self.sprite_x[j] -= 1;
+            } else {
+                self.sprite_patterns[j].0 <<= 1;
+                self.sprite_patterns[j].1 <<= 1;
+            }
+        }
+    }
 
-                let palette_color = PALETTE_ADDRESS + palette_offset as u16;
+    // With rendering off the PPU shows the backdrop, unless v points into
+    // palette RAM, in which case that color comes out instead
+    fn backdrop_address(&self) -> u16 {
+        if !self.rendering_enabled() && self.v & 0x3FFF >= PALETTE_ADDRESS {
+            self.v & 0x3FFF
+        } else {
+            PALETTE_ADDRESS
+        }
+    }
 
-                // --------------------- DRAW BACKGROUND -----------------------
+    // The pixel multiplexer: backdrop, background pixel or sprite pixel, by
+    // transparency and the sprite's priority bit
+    fn draw_pixel(&mut self, bus: &mut Bus) {
+        let x = self.cycle - 1;
+        let (mut background, background_palette) = self.background_pixel();
+        if !self
+            .mask
+            .shows(x, self.mask.background, self.mask.background_left_8)
+        {
+            background = 0;
+        }
+        let (mut sprite, sprite_palette, behind, sprite_0) = self.sprite_pixel();
+        if !self
+            .mask
+            .shows(x, self.mask.sprite, self.mask.sprite_left_8)
+        {
+            sprite = 0;
+        }
 
-                let background_draw = self.draw_tile(
-                    bus,
-                    canvas,
-                    pattern_byte_0,
-                    pattern_byte_1,
-                    n as u8,
-                    palette_color,
-                    false,
-                );
+        // Sprite 0 hit: an opaque sprite 0 pixel over an opaque background
+        // pixel, both past clipping. Never at x=255, and it stays set until
+        // the pre-render line
+        if background > 0 && sprite > 0 && sprite_0 && x != 255 && !self.status.hit {
+            self.status.hit = true;
+            self.status.write(bus);
+        }
+
+        let palette_address = if sprite > 0 && (background == 0 || !behind) {
+            SPRITE_PALETTE_ADDRESS + (sprite_palette * 4 + sprite) as u16
+        } else if background > 0 {
+            PALETTE_ADDRESS + (background_palette * 4 + background) as u16
+        } else {
+            self.backdrop_address()
+        };
+        let color = self.mask.apply_greyscale(bus.ppu_read_16(palette_address)) as u16
+            | self.mask.emphasis();
+        self.framebuffer[self.line as usize * WIDTH + x as usize] = color;
+    }
 
-                // --------------------- DRAW SPRITES ON CURRENT LINE -----------------------
-                for j in 0..self.sprite_index {
-                    let n = self.sprites[j] as usize;
-                    let x = self.oam[n * 4 + 3];
-                    let y = self.oam[n * 4];
-                    if self.cycle as u8 >= x
-                        && self.cycle as u8 <= x.wrapping_add(7)
-                        && self.line as u8 <= y.wrapping_add(7)
-                        && self.line as u8 >= y
-                    {
-                        let tile = self.oam[n * 4 + 1];
-                        let attr = self.oam[n * 4 + 2];
-                        let line = self.line as u8 - y;
-
-                        let flip_hor = get_u8_bit(attr, 6) == 1;
-                        let flip_ver = get_u8_bit(attr, 7) == 1;
-
-                        let palette_color = PALETTE_ADDRESS + (attr & 0b00000011) as u16;
-                        let mut x_offset = self.cycle as u8 - x;
-                        if flip_hor {
-                            x_offset = 7 - x_offset;
-                        }
-
-                        if self.control.sprite_size == 1 {
-                            for i in 0..2 {
-                                let (pattern_byte_0, pattern_byte_1) = self.get_pattern_address(
-                                    bus,
-                                    (tile >> 1 << 1) + i,
-                                    line as u16,
-                                    get_u8_bit(tile, 0),
-                                );
-                                self.draw_tile(
-                                    bus,
-                                    canvas,
-                                    pattern_byte_0,
-                                    pattern_byte_1,
-                                    x_offset,
-                                    palette_color,
-                                    false,
-                                );
-                                self.line += 7;
-                            }
-                            self.line -= 14;
-                        } else {
-                            let (pattern_byte_0, pattern_byte_1) = self.get_pattern_address(
-                                bus,
-                                tile,
-                                line as u16,
-                                self.control.sprite_address,
-                            );
-                            let drew = self.draw_tile(
-                                bus,
-                                canvas,
-                                pattern_byte_0,
-                                pattern_byte_1,
-                                x_offset,
-                                palette_color,
-                                false,
-                            );
-                            {
-                                if drew && n == 0 && background_draw {
-                                    self.status.hit = true;
-                                    self.status.write(bus);
-                                }
-                            }
-                        }
-                    }
+    pub fn tick(&mut self, bus: &mut Bus) {
+        if self.rendering() {
+            self.background_tick(bus);
+        }
+        if self.line < 240 && (1..=256).contains(&self.cycle) {
+            self.draw_pixel(bus);
+            if self.rendering_enabled() {
+                self.shift_sprites();
+            }
+        }
+
+        // --------------------- SPRITES FOR NEXT LINE -----------------------
+        if self.rendering() {
+            if self.cycle == 257 {
+                // Nothing is evaluated on the pre-render line, line 0 has no sprites
+                if self.line == 261 {
+                    self.sprite_count = 0;
+                } else {
+                    self.evaluate_sprites(bus);
                 }
             }
+            // OAMADDR is held at 0 while sprite tiles are fetched
+            if self.cycle >= 257 && self.cycle <= 320 {
+                self.oam_addr = 0;
+            }
+            if self.cycle >= 257 && self.cycle <= 320 && (self.cycle - 257) % 8 == 4 {
+                let j = ((self.cycle - 257) / 8) as usize;
+                self.fetch_sprite(bus, j);
+            }
         }
 
-        if self.line == 240 && self.cycle == 1 {
-            self.status.vblank = true;
+        if self.line == 241 && self.cycle == 1 {
+            self.status.vblank = !std::mem::take(&mut self.skip_vblank);
             self.status.write(bus);
-            if self.control.nmi {
-                cpu.NMI(bus);
-                cycles = 7;
-            }
         } else if self.line == 261 && self.cycle == 1 {
             self.status.vblank = false;
+            self.status.hit = false;
+            self.status.overflow = false;
             self.status.write(bus);
         }
 
@@ -415,8 +684,10 @@ impl Ppu {
         }
         if self.line > 261 {
             self.line = 0;
-            self.sprite_index = 0;
+            self.frame += 1;
         }
-        cycles
+
+        // /NMI is held low while vblank is flagged and NMI output is enabled
+        bus.nmi = self.status.vblank && self.control.nmi;
     }
 }
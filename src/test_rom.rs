@@ -0,0 +1,114 @@
+use crate::cartridge::CartridgeError;
+use crate::Bus;
+use crate::Emulator;
+
+use std::fmt;
+
+// Blargg's (and kevtris') test ROMs report through PRG RAM: $6001-$6003 hold
+// a signature once the protocol is live, $6000 the status and $6004 on a
+// zero-terminated ASCII message
+const STATUS_ADDRESS: u16 = 0x6000;
+const SIGNATURE_ADDRESS: u16 = 0x6001;
+const MESSAGE_ADDRESS: u16 = 0x6004;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_NEEDS_RESET: u8 = 0x81;
+
+const CPU_CLOCK: u64 = 1_789_773;
+// The ROM wants reset pressed no sooner than 100ms after asking for it
+const RESET_DELAY: u64 = CPU_CLOCK / 10;
+// Polling PRG RAM every cycle is needlessly slow
+const POLL_INTERVAL: u64 = 1024;
+
+#[derive(Debug)]
+pub enum TestRomError {
+    Cartridge(CartridgeError),
+    // Out of time; holds whatever message had been written so far
+    Timeout(String),
+}
+
+impl fmt::Display for TestRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestRomError::Cartridge(why) => write!(f, "{}", why),
+            TestRomError::Timeout(message) => write!(f, "timed out: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for TestRomError {}
+
+impl From<CartridgeError> for TestRomError {
+    fn from(why: CartridgeError) -> TestRomError {
+        TestRomError::Cartridge(why)
+    }
+}
+
+pub struct TestRomResult {
+    // 0 is a pass, anything else is the test's error code
+    pub code: u8,
+    pub message: String,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+fn signature_present(bus: &mut Bus) -> bool {
+    (0..3).all(|n| bus.cpu_peek(SIGNATURE_ADDRESS + n) == SIGNATURE[n as usize])
+}
+
+fn read_message(bus: &mut Bus) -> String {
+    let mut message = String::new();
+    let mut addr = MESSAGE_ADDRESS;
+    while addr < 0x8000 {
+        let c = bus.cpu_peek(addr);
+        if c == 0 {
+            break;
+        }
+        message.push(c as char);
+        addr += 1;
+    }
+    message.trim().to_string()
+}
+
+// Boot a test ROM with no window and run it until it reports a result or
+// `max_seconds` of emulated time pass
+pub fn run_test_rom(path: &str, max_seconds: u64) -> Result<TestRomResult, TestRomError> {
+    let mut emulator = Emulator::new();
+    emulator.load_rom(&std::fs::read(path).map_err(CartridgeError::from)?)?;
+
+    let mut reset_at = None;
+    // $81 stays up for a while after the reset, don't press it twice
+    let mut reset_done = false;
+    for cycle in 0..max_seconds * CPU_CLOCK {
+        emulator.clock();
+        let bus = &mut emulator.bus;
+
+        if cycle % POLL_INTERVAL != 0 || !signature_present(bus) {
+            continue;
+        }
+        match bus.cpu_peek(STATUS_ADDRESS) {
+            STATUS_RUNNING => reset_done = false,
+            STATUS_NEEDS_RESET if !reset_done => match reset_at {
+                None => reset_at = Some(cycle + RESET_DELAY),
+                Some(at) if cycle >= at => {
+                    emulator.reset();
+                    reset_at = None;
+                    reset_done = true;
+                }
+                _ => (),
+            },
+            STATUS_NEEDS_RESET => (),
+            code => {
+                return Ok(TestRomResult {
+                    code,
+                    message: read_message(bus),
+                })
+            }
+        }
+    }
+    Err(TestRomError::Timeout(read_message(&mut emulator.bus)))
+}
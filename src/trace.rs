@@ -0,0 +1,100 @@
+use crate::cpu::Addressing::*;
+use crate::cpu::Instructions;
+use crate::opcodes::{disassemble, OPCODES};
+use crate::util::*;
+use crate::Bus;
+use crate::Cpu;
+use crate::Ppu;
+
+// APU and I/O registers are mostly write-only; Nintendulator shows them as $FF
+fn peek(bus: &mut Bus, addr: u16) -> u8 {
+    if (0x4000..0x4020).contains(&addr) {
+        0xFF
+    } else {
+        bus.cpu_peek(addr)
+    }
+}
+
+// What Nintendulator shows after the operand: the effective address and the
+// value sitting there. Memory is peeked so tracing has no side effects.
+fn annotation(cpu: &Cpu, bus: &mut Bus, pc: u16) -> String {
+    let opcode = &OPCODES[peek(bus, pc) as usize];
+    let low = peek(bus, pc.wrapping_add(1));
+    let high = peek(bus, pc.wrapping_add(2));
+    let word = combine_low_high(low, high);
+    let jump = matches!(opcode.instr, Instructions::JMP | Instructions::JSR);
+    match opcode.addr {
+        IMP | ACC | IMM | REL => String::new(),
+        ZPG => format!(" = {:02X}", peek(bus, low as u16)),
+        ZPX | ZPY => {
+            let index = if opcode.addr == ZPX { cpu.x } else { cpu.y };
+            let addr = low.wrapping_add(index) as u16;
+            format!(" @ {:02X} = {:02X}", addr, peek(bus, addr))
+        }
+        ABS if jump => String::new(),
+        ABS => format!(" = {:02X}", peek(bus, word)),
+        ABX | ABY => {
+            let index = if opcode.addr == ABX { cpu.x } else { cpu.y };
+            let addr = word.wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", addr, peek(bus, addr))
+        }
+        IND => {
+            // Same page wrap as the real JMP ($xxFF)
+            let high_addr = (word & 0xFF00) | (word as u8).wrapping_add(1) as u16;
+            let target = combine_low_high(peek(bus, word), peek(bus, high_addr));
+            format!(" = {:04X}", target)
+        }
+        IDX => {
+            let pointer = low.wrapping_add(cpu.x);
+            let addr = combine_low_high(
+                peek(bus, pointer as u16),
+                peek(bus, pointer.wrapping_add(1) as u16),
+            );
+            format!(
+                " @ {:02X} = {:04X} = {:02X}",
+                pointer,
+                addr,
+                peek(bus, addr)
+            )
+        }
+        IDY => {
+            let base =
+                combine_low_high(peek(bus, low as u16), peek(bus, low.wrapping_add(1) as u16));
+            let addr = base.wrapping_add(cpu.y as u16);
+            format!(" = {:04X} @ {:04X} = {:02X}", base, addr, peek(bus, addr))
+        }
+    }
+}
+
+// One line of a Nintendulator/nestest log for the instruction at the cpu's
+// pc, e.g.
+// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+pub fn nestest_line(cpu: &Cpu, ppu: &Ppu, bus: &mut Bus) -> String {
+    let pc = cpu.pc;
+    let opcode = &OPCODES[bus.cpu_peek(pc) as usize];
+    let bytes: Vec<u8> = (0..opcode.len as u16)
+        .map(|n| bus.cpu_peek(pc.wrapping_add(n)))
+        .collect();
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+    // unofficial opcodes get their '*' in the column before the mnemonic
+    let disassembly = format!(
+        "{}{}{}",
+        if opcode.official { " " } else { "" },
+        disassemble(&bytes, pc),
+        annotation(cpu, bus, pc)
+    );
+    format!(
+        "{:04X}  {:<9}{:<33}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        pc,
+        hex.join(" "),
+        disassembly,
+        cpu.a,
+        cpu.x,
+        cpu.y,
+        cpu.flags_to_byte(),
+        cpu.stack_pointer,
+        ppu.line,
+        ppu.cycle,
+        cpu.cycles
+    )
+}
@@ -7,7 +7,9 @@ pub const SCROLL: u16 = 0x2005;
 pub const ADDR: u16 = 0x2006;
 pub const DATA: u16 = 0x2007;
 pub const OAM_DMA: u16 = 0x4014;
+pub const APU_STATUS: u16 = 0x4015;
 pub const INPUT: u16 = 0x4016;
+pub const INPUT_2: u16 = 0x4017;
 
 // Little endian conversion
 pub fn combine_low_high(low: u8, high: u8) -> u16 {
@@ -0,0 +1,159 @@
+// Checks the iNES 1.0 / NES 2.0 header parser on hand-built images.
+
+use nes::cartridge::{Cartridge, CartridgeError, ConsoleType, HeaderFormat, Timing};
+use nes::mapper::Mirroring;
+
+// A header with the given bytes 4-15, followed by `body` filler bytes
+fn image(header: [u8; 12], body: usize) -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A];
+    rom.extend_from_slice(&header);
+    rom.extend((0..body).map(|n| n as u8));
+    rom
+}
+
+fn parse(rom: &[u8]) -> Cartridge {
+    match Cartridge::from_bytes(rom) {
+        Ok(cartridge) => cartridge,
+        Err(why) => panic!("{}", why),
+    }
+}
+
+#[test]
+fn rejects_bad_files() {
+    assert!(matches!(
+        Cartridge::from_bytes(b"NES"),
+        Err(CartridgeError::TooShort(3))
+    ));
+
+    let mut rom = image([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x4000);
+    rom[3] = 0x1B;
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::BadMagic([b'N', b'E', b'S', 0x1B]))
+    ));
+
+    let rom = image([1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x4000);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::Truncated {
+            expected: 0x6010,
+            actual: 0x4010
+        })
+    ));
+
+    let rom = image([0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x2000);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::NoPrgRom)
+    ));
+}
+
+#[test]
+fn rejects_sizes_that_overflow() {
+    // NES 2.0 exponent-multiplier PRG size of 2^63 * 7
+    let rom = image([0xFF, 0, 0, 0x08, 0, 0x0F, 0, 0, 0, 0, 0, 0], 0);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::SizeOverflow)
+    ));
+    // 2^63 PRG plus 2^63 CHR
+    let rom = image([0xFC, 0xFC, 0, 0x08, 0, 0xFF, 0, 0, 0, 0, 0, 0], 0);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::SizeOverflow)
+    ));
+}
+
+#[test]
+fn ines() {
+    // 2 PRG banks, 1 CHR bank, vertical, battery, mapper $41, PAL
+    let rom = image([2, 1, 0x13, 0x40, 0, 1, 0, 0, 0, 0, 0, 0], 0xA000);
+    let cartridge = parse(&rom);
+    assert_eq!(cartridge.format, HeaderFormat::INes);
+    assert_eq!(cartridge.mapper, 0x41);
+    assert_eq!(cartridge.mirroring, Mirroring::Vertical);
+    assert!(cartridge.battery);
+    assert_eq!(cartridge.timing, Timing::Pal);
+    assert_eq!(cartridge.console_type, ConsoleType::Nes);
+    assert_eq!(cartridge.prg_rom.len(), 0x8000);
+    assert_eq!(cartridge.prg_rom[0], rom[16]);
+    assert_eq!(cartridge.chr_rom.len(), 0x2000);
+    assert_eq!(cartridge.chr_rom[0], rom[16 + 0x8000]);
+    // byte 8 of 0 still means 8K, battery backed here
+    assert_eq!(cartridge.prg_ram_size, 0);
+    assert_eq!(cartridge.prg_nvram_size, 0x2000);
+    assert_eq!(cartridge.chr_ram_size, 0);
+}
+
+#[test]
+fn ines_prg_ram_default() {
+    let cartridge = parse(&image([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x4000));
+    assert_eq!(cartridge.prg_ram_size, 0x2000);
+    assert_eq!(cartridge.chr_ram_size, 0x2000);
+    assert!(cartridge.has_chr_ram());
+
+    let cartridge = parse(&image([1, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0], 0x4000));
+    assert_eq!(cartridge.prg_ram_size, 0x8000);
+}
+
+#[test]
+fn dirty_header_drops_mapper_high_nibble() {
+    let mut header = [1, 0, 0x10, 0x40, 0, 0, 0, 0, 0, 0, 0, 0];
+    header[3..12].copy_from_slice(b"@DiskDude");
+    let cartridge = parse(&image(header, 0x4000));
+    assert_eq!(cartridge.format, HeaderFormat::INes);
+    assert_eq!(cartridge.mapper, 1);
+}
+
+#[test]
+fn trainer_comes_before_prg() {
+    let rom = image([1, 0, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0], 0x200 + 0x4000);
+    let cartridge = parse(&rom);
+    let trainer = cartridge.trainer.unwrap();
+    assert_eq!(trainer.len(), 0x200);
+    assert_eq!(trainer[..], rom[16..16 + 0x200]);
+    assert_eq!(cartridge.prg_rom[..], rom[16 + 0x200..]);
+}
+
+#[test]
+fn nes2() {
+    // mapper $1A3 submapper 5, four-screen, PRG RAM 64 << 7, CHR NVRAM
+    // 64 << 4, Dendy, extended console type 3
+    let rom = image([2, 1, 0x38, 0xAB, 0x51, 0, 0x07, 0x40, 3, 3, 0, 0], 0xA000);
+    let cartridge = parse(&rom);
+    assert_eq!(cartridge.format, HeaderFormat::Nes2);
+    assert_eq!(cartridge.mapper, 0x1A3);
+    assert_eq!(cartridge.submapper, 5);
+    assert_eq!(cartridge.mirroring, Mirroring::FourScreen);
+    assert_eq!(cartridge.prg_ram_size, 0x2000);
+    assert_eq!(cartridge.prg_nvram_size, 0);
+    assert_eq!(cartridge.chr_ram_size, 0);
+    assert_eq!(cartridge.chr_nvram_size, 0x400);
+    assert_eq!(cartridge.timing, Timing::Dendy);
+    assert_eq!(cartridge.console_type, ConsoleType::Extended(3));
+    assert_eq!(cartridge.prg_rom.len(), 0x8000);
+    assert_eq!(cartridge.chr_rom.len(), 0x2000);
+
+    // only byte 7 bits 2-3 = 10 mark NES 2.0
+    let rom = image([1, 0, 0, 0x0C, 0, 0, 0, 0, 0, 0, 0, 0], 0x4000);
+    assert_eq!(parse(&rom).format, HeaderFormat::INes);
+}
+
+#[test]
+fn nes2_exponent_multiplier_sizes() {
+    // PRG 2^13 * 3, CHR 2^10 * 1
+    let rom = image(
+        [0x35, 0x28, 0, 0x08, 0, 0xFF, 0, 0, 0, 0, 0, 0],
+        0x6000 + 0x400,
+    );
+    let cartridge = parse(&rom);
+    assert_eq!(cartridge.prg_rom.len(), 0x6000);
+    assert_eq!(cartridge.chr_rom.len(), 0x400);
+
+    // 12 bit bank counts otherwise: $102 PRG banks
+    let rom = image(
+        [0x02, 0, 0, 0x08, 0, 0x01, 0, 0, 0, 0, 0, 0],
+        0x102 * 0x4000,
+    );
+    assert_eq!(parse(&rom).prg_rom.len(), 0x102 * 0x4000);
+}
@@ -0,0 +1,33 @@
+// Helpers shared by the integration tests. Each test crate only uses some of
+// them.
+#![allow(dead_code)]
+
+use nes::{Bus, Emulator};
+
+// A 16K NROM image with 8K of CHR, the given header byte 6 (mirroring and
+// four-screen bits) and `program` at $8000, where the reset vector points
+pub fn nrom(flags_6: u8, program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, flags_6];
+    rom.resize(16 + 0x4000 + 0x2000, 0);
+    rom[16..16 + program.len()].copy_from_slice(program);
+    rom[16 + 0x3FFD] = 0x80;
+    rom
+}
+
+// A bus with an empty NROM cartridge using the given header byte 6
+pub fn bus_with_flags(flags_6: u8) -> Bus {
+    let mut bus = Bus::new();
+    if let Err(why) = bus.load_rom(&nrom(flags_6, &[])) {
+        panic!("{}", why);
+    }
+    bus
+}
+
+// A powered on console running `program` from $8000
+pub fn emulator_with_program(program: &[u8]) -> Emulator {
+    let mut emulator = Emulator::new();
+    if let Err(why) = emulator.load_rom(&nrom(0, program)) {
+        panic!("{}", why);
+    }
+    emulator
+}
@@ -0,0 +1,73 @@
+// Checks the CPU address decoder: RAM mirroring, the PPU register mirrors
+// and which regions take writes.
+
+mod common;
+
+use common::emulator_with_program;
+use nes::{Bus, Ppu};
+
+#[test]
+fn ram_mirroring() {
+    let mut bus = Bus::new();
+    bus.cpu_write_16(0x0012, 0x34);
+    for mirror in [0x0812, 0x1012, 0x1812] {
+        assert_eq!(bus.cpu_read_16(mirror), 0x34);
+    }
+    bus.cpu_write_16(0x1FFF, 0x56);
+    assert_eq!(bus.cpu_read_16(0x07FF), 0x56);
+}
+
+#[test]
+fn ppu_register_mirrors() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    // $3456 is $2006, $3FFF is $2007
+    bus.cpu_write_16_ppu_regs(0x3456, 0x21, &mut ppu);
+    bus.cpu_write_16_ppu_regs(0x200E, 0x08, &mut ppu);
+    assert_eq!(ppu.v, 0x2108);
+    bus.cpu_write_16_ppu_regs(0x3FFF, 0x77, &mut ppu);
+    assert_eq!(bus.ppu_read_16(0x2108), 0x77);
+    assert_eq!(ppu.v, 0x2109);
+}
+
+#[test]
+fn rom_and_test_mode_ignore_writes() {
+    let mut bus = Bus::new();
+    let rom = bus.cpu_read_16(0x8000);
+    bus.cpu_write_16(0x8000, !rom);
+    assert_eq!(bus.cpu_read_16(0x8000), rom);
+    bus.cpu_write_16(0x401A, 0xFF);
+    assert_eq!(bus.cpu_read_16(0x401A), bus.open_bus);
+}
+
+#[test]
+fn open_bus() {
+    // LDA $4016; STA $00; LDA $5000; STA $01; LDX #$02; LDA $4018,X; STA $02
+    #[rustfmt::skip]
+    let program = [
+        0xAD, 0x16, 0x40, 0x85, 0x00,
+        0xAD, 0x00, 0x50, 0x85, 0x01,
+        0xA2, 0x02, 0xBD, 0x18, 0x40, 0x85, 0x02,
+    ];
+    let mut emulator = emulator_with_program(&program);
+    emulator.set_buttons(0, 0x01);
+    for _ in 0..8 {
+        emulator.step_instruction();
+    }
+    let ram = &emulator.bus.ram;
+    // the high byte of the operand is the last thing on the bus
+    assert_eq!(ram[0] & 0xE0, 0x40);
+    assert_eq!(ram[1], 0x50);
+    // test mode registers ($401A here) are undriven too
+    assert_eq!(ram[2], 0x40);
+}
+
+#[test]
+fn apu_status_reads_leave_open_bus() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    bus.open_bus = 0x64;
+    // only bit 5 is undriven
+    assert_eq!(bus.cpu_read_16_ppu_regs(0x4015, &mut ppu), 0x20);
+    assert_eq!(bus.open_bus, 0x64);
+    assert_eq!(bus.cpu_read_16_ppu_regs(0x4017, &mut ppu), 0x60);
+    assert_eq!(bus.open_bus, 0x60);
+}
@@ -0,0 +1,203 @@
+// The cpu's interrupt handling on a stub bus whose /NMI and /IRQ lines the
+// tests drive by hand: BRK/IRQ/NMI sequences, the I flag and its delayed
+// changes, NMI edge detection and vector hijacking.
+
+use nes::bus::{IRQ_DMC, IRQ_FRAME_COUNTER};
+use nes::cpu::{Cpu, CpuBus};
+use nes::Bus;
+
+const NMI_HANDLER: u16 = 0x0300;
+const IRQ_HANDLER: u16 = 0x0400;
+
+// Flat 64K of RAM full of NOPs, and the two interrupt lines
+struct TestBus {
+    ram: Vec<u8>,
+    nmi: bool,
+    irq: bool,
+}
+
+impl CpuBus for TestBus {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        self.ram[addr as usize] = val;
+    }
+
+    fn nmi(&self) -> bool {
+        self.nmi
+    }
+
+    fn irq(&self) -> bool {
+        self.irq
+    }
+}
+
+// `program` at $0200 with the cpu about to run it, I clear and the stack
+// pointer at $FD
+fn setup(program: &[u8]) -> (Cpu, TestBus) {
+    let mut bus = TestBus {
+        ram: vec![0xEA; 0x10000],
+        nmi: false,
+        irq: false,
+    };
+    bus.ram[0x0200..0x0200 + program.len()].copy_from_slice(program);
+    bus.ram[0xFFFA..0xFFFC].copy_from_slice(&NMI_HANDLER.to_le_bytes());
+    bus.ram[0xFFFE..].copy_from_slice(&IRQ_HANDLER.to_le_bytes());
+
+    let mut cpu = Cpu::new();
+    cpu.pc = 0x0200;
+    cpu.stack_pointer = 0xFD;
+    cpu.flags_from_byte(0x00);
+    (cpu, bus)
+}
+
+// The return address and status an interrupt sequence pushed
+fn pushed(bus: &TestBus) -> (u16, u8) {
+    let pc = u16::from_le_bytes([bus.ram[0x01FC], bus.ram[0x01FD]]);
+    (pc, bus.ram[0x01FB])
+}
+
+#[test]
+fn brk_pushes_b_set() {
+    let (mut cpu, mut bus) = setup(&[0x00, 0xFF]);
+    assert_eq!(cpu.step_instruction(&mut bus), 7);
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert!(cpu.i);
+    // the padding byte is skipped over
+    assert_eq!(pushed(&bus), (0x0202, 0x30));
+}
+
+#[test]
+fn irq_pushes_b_clear() {
+    let (mut cpu, mut bus) = setup(&[]);
+    bus.irq = true;
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, 0x0201);
+    assert_eq!(cpu.step_instruction(&mut bus), 7);
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert!(cpu.i);
+    assert_eq!(pushed(&bus), (0x0201, 0x20));
+}
+
+#[test]
+fn nmi_pushes_b_clear() {
+    let (mut cpu, mut bus) = setup(&[]);
+    bus.nmi = true;
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.step_instruction(&mut bus), 7);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed(&bus), (0x0201, 0x20));
+}
+
+#[test]
+fn irq_waits_for_i_clear() {
+    let (mut cpu, mut bus) = setup(&[]);
+    cpu.flags_from_byte(0x04);
+    bus.irq = true;
+    for _ in 0..3 {
+        cpu.step_instruction(&mut bus);
+    }
+    assert_eq!(cpu.pc, 0x0203);
+}
+
+#[test]
+fn cli_and_plp_take_effect_an_instruction_late() {
+    // CLI: the NOP after it still runs before the IRQ
+    let (mut cpu, mut bus) = setup(&[0x58]);
+    cpu.flags_from_byte(0x04);
+    bus.irq = true;
+    cpu.step_instruction(&mut bus);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, 0x0202);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert_eq!(pushed(&bus).0, 0x0202);
+
+    // PLP pulling I clear, the same
+    let (mut cpu, mut bus) = setup(&[0x28]);
+    cpu.flags_from_byte(0x04);
+    cpu.stack_pointer = 0xFC;
+    bus.ram[0x01FD] = 0x00;
+    bus.irq = true;
+    cpu.step_instruction(&mut bus);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, 0x0202);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+}
+
+#[test]
+fn sei_lets_one_irq_through() {
+    // the IRQ was polled before SEI set I, so it's taken right after, and
+    // pushes I set
+    let (mut cpu, mut bus) = setup(&[0x78]);
+    bus.irq = true;
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, 0x0201);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, IRQ_HANDLER);
+    assert_eq!(pushed(&bus), (0x0201, 0x24));
+}
+
+#[test]
+fn nmi_is_edge_triggered() {
+    let (mut cpu, mut bus) = setup(&[]);
+    bus.nmi = true;
+    cpu.step_instruction(&mut bus);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    // held low, it doesn't fire again
+    for _ in 0..3 {
+        cpu.step_instruction(&mut bus);
+    }
+    assert_eq!(cpu.pc, NMI_HANDLER + 3);
+
+    // released and asserted again, it does
+    bus.nmi = false;
+    cpu.step_instruction(&mut bus);
+    bus.nmi = true;
+    cpu.step_instruction(&mut bus);
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+}
+
+#[test]
+fn nmi_hijacks_brk_and_irq() {
+    // an NMI arriving before BRK picks its vector sends it to $FFFA, with B
+    // still pushed set
+    let (mut cpu, mut bus) = setup(&[0x00, 0xFF]);
+    cpu.step(&mut bus);
+    cpu.step(&mut bus);
+    bus.nmi = true;
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed(&bus), (0x0202, 0x30));
+    // and the NMI is used up
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, NMI_HANDLER + 1);
+
+    // the same for an IRQ sequence, with B clear
+    let (mut cpu, mut bus) = setup(&[]);
+    bus.irq = true;
+    cpu.step_instruction(&mut bus);
+    cpu.step(&mut bus);
+    cpu.step(&mut bus);
+    bus.nmi = true;
+    cpu.step_instruction(&mut bus);
+    assert_eq!(cpu.pc, NMI_HANDLER);
+    assert_eq!(pushed(&bus), (0x0201, 0x20));
+}
+
+#[test]
+fn irq_sources_share_the_line() {
+    let mut bus = Bus::new();
+    assert!(!bus.irq());
+    bus.set_irq(IRQ_FRAME_COUNTER, true);
+    bus.set_irq(IRQ_DMC, true);
+    bus.set_irq(IRQ_FRAME_COUNTER, false);
+    assert!(bus.irq());
+    bus.set_irq(IRQ_DMC, false);
+    assert!(!bus.irq());
+}
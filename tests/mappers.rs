@@ -0,0 +1,316 @@
+// Drives the boards' bank switching and IRQ state machines through the
+// Mapper trait. Every PRG byte holds its 8K bank number and every CHR byte
+// its 1K bank number, so a read shows which bank is mapped in.
+
+use nes::cartridge::{Cartridge, CartridgeError};
+use nes::mapper::{self, Mapper, Mirroring};
+use nes::Emulator;
+
+// A NES 2.0 image with 8K of PRG RAM, and 8K of CHR RAM when chr_8k is 0
+fn image(mapper: u16, submapper: u8, prg_16k: u8, chr_8k: u8) -> Vec<u8> {
+    let mut rom = vec![
+        b'N',
+        b'E',
+        b'S',
+        0x1A,
+        prg_16k,
+        chr_8k,
+        ((mapper & 0x0F) << 4) as u8,
+        0x08 | (mapper & 0xF0) as u8,
+        submapper << 4 | (mapper >> 8) as u8,
+        0,
+        0x07,
+        if chr_8k == 0 { 0x07 } else { 0 },
+        0,
+        0,
+        0,
+        0,
+    ];
+    rom.extend((0..prg_16k as usize * 0x4000).map(|n| (n / 0x2000) as u8));
+    rom.extend((0..chr_8k as usize * 0x2000).map(|n| (n / 0x400) as u8));
+    rom
+}
+
+fn board(rom: &[u8]) -> Box<dyn Mapper> {
+    match Cartridge::from_bytes(rom).and_then(mapper::from_cartridge) {
+        Ok(board) => board,
+        Err(why) => panic!("{}", why),
+    }
+}
+
+// ---------------------------- MMC1 ---------------------------------
+
+// Five serial writes, LSB first, with idle cycles between them
+fn mmc1_write(board: &mut Box<dyn Mapper>, addr: u16, val: u8) {
+    for bit in 0..5 {
+        board.cpu_write(addr, val >> bit & 0x01);
+        board.cpu_clock();
+        board.cpu_clock();
+    }
+}
+
+#[test]
+fn mmc1_prg_modes() {
+    let mut mmc1 = board(&image(1, 0, 8, 2));
+    // power on: 16K at $8000 switchable, last bank fixed at $C000
+    mmc1_write(&mut mmc1, 0xE000, 3);
+    assert_eq!(mmc1.cpu_read(0x8000), Some(6));
+    assert_eq!(mmc1.cpu_read(0xC000), Some(14));
+
+    // first bank fixed at $8000, switchable at $C000
+    mmc1_write(&mut mmc1, 0x8000, 0x08);
+    assert_eq!(mmc1.cpu_read(0x8000), Some(0));
+    assert_eq!(mmc1.cpu_read(0xC000), Some(6));
+
+    // 32K, ignoring the low bit of the bank number
+    mmc1_write(&mut mmc1, 0x8000, 0x00);
+    assert_eq!(mmc1.cpu_read(0x8000), Some(4));
+    assert_eq!(mmc1.cpu_read(0xC000), Some(6));
+}
+
+#[test]
+fn mmc1_chr_modes_and_mirroring() {
+    let mut mmc1 = board(&image(1, 0, 2, 16));
+    // 8K, ignoring the low bit of the bank number, vertical mirroring
+    mmc1_write(&mut mmc1, 0x8000, 0x02);
+    mmc1_write(&mut mmc1, 0xA000, 3);
+    assert_eq!(mmc1.ppu_read(0x0000), 8);
+    assert_eq!(mmc1.ppu_read(0x1000), 12);
+    assert_eq!(mmc1.mirroring(), Mirroring::Vertical);
+
+    // two 4K banks, single screen
+    mmc1_write(&mut mmc1, 0x8000, 0x11);
+    mmc1_write(&mut mmc1, 0xC000, 5);
+    assert_eq!(mmc1.ppu_read(0x0000), 12);
+    assert_eq!(mmc1.ppu_read(0x1000), 20);
+    assert_eq!(mmc1.mirroring(), Mirroring::SingleScreenUpper);
+}
+
+#[test]
+fn mmc1_reset_bit() {
+    let mut mmc1 = board(&image(1, 0, 8, 2));
+    mmc1_write(&mut mmc1, 0x8000, 0x00);
+    // a write with bit 7 set drops the bits shifted in so far and goes
+    // back to the fixed last bank
+    mmc1.cpu_write(0xE000, 0x01);
+    mmc1.cpu_clock();
+    mmc1.cpu_clock();
+    mmc1.cpu_write(0x8000, 0x80);
+    mmc1.cpu_clock();
+    mmc1.cpu_clock();
+    mmc1_write(&mut mmc1, 0xE000, 2);
+    assert_eq!(mmc1.cpu_read(0x8000), Some(4));
+    assert_eq!(mmc1.cpu_read(0xC000), Some(14));
+}
+
+#[test]
+fn mmc1_ignores_consecutive_writes() {
+    let mut mmc1 = board(&image(1, 0, 8, 2));
+    mmc1.cpu_write(0xE000, 0x00);
+    mmc1.cpu_clock();
+    // the very next cycle: dropped
+    mmc1.cpu_write(0xE000, 0x00);
+    mmc1.cpu_clock();
+    for _ in 0..4 {
+        mmc1.cpu_clock();
+        mmc1.cpu_write(0xE000, 0x01);
+        mmc1.cpu_clock();
+    }
+    // bits 0, 1, 1, 1, 1: bank 30, or 28 had the second write counted
+    assert_eq!(mmc1.cpu_read(0x8000), Some(12));
+}
+
+#[test]
+fn mmc1_read_modify_write() {
+    // INC $E000 writes the old value back, then the new one on the next
+    // cycle; only the first reaches the serial port.
+    // INC $E000; LDA #0; STA $E000 x4; LDA $8000; STA $00
+    #[rustfmt::skip]
+    let program = [
+        0xEE, 0x00, 0xE0, 0xA9, 0x00,
+        0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0,
+        0x8D, 0x00, 0xE0, 0x8D, 0x00, 0xE0,
+        0xAD, 0x00, 0x80, 0x85, 0x00,
+    ];
+    let mut rom = image(1, 0, 8, 2);
+    // the program, $E000 and the vectors all sit in the fixed last bank
+    let last_bank = 16 + 7 * 0x4000;
+    rom[last_bank..last_bank + program.len()].copy_from_slice(&program);
+    rom[last_bank + 0x2000] = 0x00;
+    rom[last_bank + 0x3FFC] = 0x00;
+    rom[last_bank + 0x3FFD] = 0xC0;
+
+    let mut emulator = Emulator::new();
+    if let Err(why) = emulator.load_rom(&rom) {
+        panic!("{}", why);
+    }
+    for _ in 0..9 {
+        emulator.step_instruction();
+    }
+    // bank 0; had the INC's second write counted it would be bank 2
+    assert_eq!(emulator.bus.ram[0], 0);
+}
+
+// ------------------------ DISCRETE LOGIC -----------------------------
+
+#[test]
+fn uxrom_banks_and_bus_conflicts() {
+    let mut uxrom = board(&image(2, 0, 8, 0));
+    assert_eq!(uxrom.cpu_read(0xC000), Some(14));
+    // the ROM drives $0E at $C000, ANDed with the written $07
+    uxrom.cpu_write(0xC000, 0x07);
+    assert_eq!(uxrom.cpu_read(0x8000), Some(12));
+    assert_eq!(uxrom.cpu_read(0xC000), Some(14));
+
+    let mut uxrom = board(&image(2, 1, 8, 0));
+    uxrom.cpu_write(0xC000, 0x07);
+    assert_eq!(uxrom.cpu_read(0x8000), Some(14));
+}
+
+#[test]
+fn small_prg_roms() {
+    // no PRG at all is refused up front
+    let rom = image(2, 0, 0, 0);
+    assert!(matches!(
+        Cartridge::from_bytes(&rom),
+        Err(CartridgeError::NoPrgRom)
+    ));
+
+    // 8K of PRG (NES 2.0 exponent-multiplier 2^13) is one bank, mirrored
+    for mapper in [1, 2, 4] {
+        let mut rom = image(mapper, 0, 0, 0);
+        rom[4] = 13 << 2;
+        rom[9] = 0x0F;
+        rom.extend(vec![0xEA; 0x2000]);
+        let mut board = board(&rom);
+        for addr in [0x8000, 0xA000, 0xC000, 0xE000, 0xFFFF] {
+            assert_eq!(board.cpu_read(addr), Some(0xEA));
+        }
+    }
+}
+
+#[test]
+fn cnrom_banks_and_bus_conflicts() {
+    let mut cnrom = board(&image(3, 0, 1, 4));
+    // the ROM drives $01 at $A000
+    cnrom.cpu_write(0xA000, 0x03);
+    assert_eq!(cnrom.ppu_read(0x0000), 8);
+
+    let mut cnrom = board(&image(3, 1, 1, 4));
+    cnrom.cpu_write(0xA000, 0x03);
+    assert_eq!(cnrom.ppu_read(0x0000), 24);
+}
+
+#[test]
+fn gxrom_banks_and_bus_conflicts() {
+    let mut rom = image(66, 0, 8, 4);
+    rom[16 + 0x6000] = 0x33;
+    let mut gxrom = board(&rom);
+    // $3F & $33: PRG bank 3, CHR bank 3
+    gxrom.cpu_write(0xE000, 0x3F);
+    assert_eq!(gxrom.cpu_read(0x8000), Some(12));
+    assert_eq!(gxrom.ppu_read(0x0000), 24);
+}
+
+#[test]
+fn axrom_banks_and_single_screen() {
+    let mut axrom = board(&image(7, 0, 8, 0));
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+    axrom.cpu_write(0x8000, 0x12);
+    assert_eq!(axrom.cpu_read(0x8000), Some(8));
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenUpper);
+    axrom.cpu_write(0x8000, 0x02);
+    assert_eq!(axrom.mirroring(), Mirroring::SingleScreenLower);
+}
+
+// ---------------------------- MMC3 ---------------------------------
+
+// A12 low for `low_cycles` CPU cycles, then high
+fn a12_rise(board: &mut Box<dyn Mapper>, low_cycles: u8) {
+    board.ppu_address(0x0000);
+    for _ in 0..low_cycles {
+        board.cpu_clock();
+    }
+    board.ppu_address(0x1000);
+}
+
+#[test]
+fn mmc3_prg_modes() {
+    let mut mmc3 = board(&image(4, 0, 8, 2));
+    mmc3.cpu_write(0x8000, 6);
+    mmc3.cpu_write(0x8001, 5);
+    mmc3.cpu_write(0x8000, 7);
+    mmc3.cpu_write(0x8001, 9);
+    assert_eq!(mmc3.cpu_read(0x8000), Some(5));
+    assert_eq!(mmc3.cpu_read(0xA000), Some(9));
+    assert_eq!(mmc3.cpu_read(0xC000), Some(14));
+    assert_eq!(mmc3.cpu_read(0xE000), Some(15));
+
+    // bit 6 swaps $8000 and $C000
+    mmc3.cpu_write(0x8000, 0x40);
+    assert_eq!(mmc3.cpu_read(0x8000), Some(14));
+    assert_eq!(mmc3.cpu_read(0xC000), Some(5));
+}
+
+#[test]
+fn mmc3_irq_counter() {
+    let mut mmc3 = board(&image(4, 0, 8, 2));
+    mmc3.cpu_write(0xC000, 2);
+    mmc3.cpu_write(0xC001, 0);
+    mmc3.cpu_write(0xE001, 0);
+
+    // reload to 2, then count down to 0
+    a12_rise(&mut mmc3, 3);
+    assert!(!mmc3.irq());
+    a12_rise(&mut mmc3, 3);
+    assert!(!mmc3.irq());
+    a12_rise(&mut mmc3, 3);
+    assert!(mmc3.irq());
+
+    // $E000 acknowledges and disables
+    mmc3.cpu_write(0xE000, 0);
+    assert!(!mmc3.irq());
+    for _ in 0..3 {
+        a12_rise(&mut mmc3, 3);
+    }
+    assert!(!mmc3.irq());
+}
+
+#[test]
+fn mmc3_a12_filter() {
+    let mut mmc3 = board(&image(4, 0, 8, 2));
+    mmc3.cpu_write(0xC000, 1);
+    mmc3.cpu_write(0xC001, 0);
+    mmc3.cpu_write(0xE001, 0);
+
+    a12_rise(&mut mmc3, 3);
+    assert!(!mmc3.irq());
+    // too short a drop, as between background and sprite fetches
+    a12_rise(&mut mmc3, 1);
+    assert!(!mmc3.irq());
+    a12_rise(&mut mmc3, 3);
+    assert!(mmc3.irq());
+}
+
+#[test]
+fn mmc3_mirroring_and_prg_ram() {
+    let mut mmc3 = board(&image(4, 0, 8, 2));
+    mmc3.cpu_write(0xA000, 0x01);
+    assert_eq!(mmc3.mirroring(), Mirroring::Horizontal);
+    mmc3.cpu_write(0x6000, 0x42);
+    // write protect, then disable
+    mmc3.cpu_write(0xA001, 0xC0);
+    mmc3.cpu_write(0x6000, 0x43);
+    assert_eq!(mmc3.cpu_read(0x6000), Some(0x42));
+    mmc3.cpu_write(0xA001, 0x00);
+    assert_eq!(mmc3.cpu_read(0x6000), None);
+
+    // four-screen boards ignore $A000, and it doesn't reach $A001
+    let mut rom = image(4, 0, 8, 2);
+    rom[6] |= 0x08;
+    let mut mmc3 = board(&rom);
+    mmc3.cpu_write(0xA000, 0x00);
+    assert_eq!(mmc3.mirroring(), Mirroring::FourScreen);
+    mmc3.cpu_write(0x6000, 0x42);
+    assert_eq!(mmc3.cpu_read(0x6000), Some(0x42));
+}
@@ -0,0 +1,48 @@
+// Runs kevtris' nestest.nes in automation mode (straight from $C000, no PPU
+// needed) and diffs our trace against the Nintendulator golden log.
+
+use nes::trace::nestest_line;
+use nes::Emulator;
+
+const ROM: &str = "src/testing/nestest.nes";
+const LOG: &str = "src/testing/nogfx/nestest.log";
+
+#[test]
+fn nestest() {
+    let root = env!("CARGO_MANIFEST_DIR");
+    let mut emulator = Emulator::new();
+    let rom = std::fs::read(format!("{}/{}", root, ROM)).unwrap();
+    if let Err(why) = emulator.load_rom(&rom) {
+        panic!("{}", why);
+    }
+    let log = std::fs::read_to_string(format!("{}/{}", root, LOG)).unwrap();
+
+    // run the reset sequence, then jump to the automated entry point
+    emulator.step_instruction();
+    emulator.cpu.pc = 0xC000;
+
+    for (n, expected) in log.lines().enumerate() {
+        let line = nestest_line(&emulator.cpu, &emulator.ppu, &mut emulator.bus);
+        if line != expected {
+            panic!(
+                "trace diverges at line {}\nexpected: {}\n     got: {}",
+                n + 1,
+                expected,
+                line
+            );
+        }
+        emulator.step_instruction();
+    }
+
+    // nestest leaves its error codes for the official and unofficial opcodes here
+    assert_eq!(
+        emulator.bus.cpu_peek(0x02),
+        0,
+        "official opcode test failed"
+    );
+    assert_eq!(
+        emulator.bus.cpu_peek(0x03),
+        0,
+        "unofficial opcode test failed"
+    );
+}
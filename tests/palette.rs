@@ -0,0 +1,51 @@
+// Loading .pal files and the emphasis entries built for 64 color ones.
+
+use nes::palette::{Palette, PaletteError};
+
+fn load(pal: &[u8]) -> Palette {
+    match Palette::from_bytes(pal) {
+        Ok(palette) => palette,
+        Err(why) => panic!("{}", why),
+    }
+}
+
+#[test]
+fn sixty_four_colors() {
+    let pal: Vec<u8> = (0..64 * 3).map(|n| n as u8).collect();
+    let palette = load(&pal);
+    assert_eq!(palette.rgb(0x00), (0, 1, 2));
+    assert_eq!(palette.rgb(0x3F), (189, 190, 191));
+}
+
+#[test]
+fn five_hundred_twelve_colors() {
+    // every entry is used as is, emphasis ones included
+    let pal: Vec<u8> = (0..512 * 3).map(|n| (n / 3) as u8).collect();
+    let palette = load(&pal);
+    assert_eq!(palette.rgb(0x1C5), (0xC5, 0xC5, 0xC5));
+    assert_eq!(palette.rgb(0x0FF), (0xFF, 0xFF, 0xFF));
+}
+
+#[test]
+fn rejects_other_sizes() {
+    for len in [0, 64 * 3 - 1, 65 * 3, 512 * 3 + 3] {
+        assert!(matches!(
+            Palette::from_bytes(&vec![0; len]),
+            Err(PaletteError::BadSize(n)) if n == len
+        ));
+    }
+}
+
+#[test]
+fn synthesized_emphasis() {
+    let pal: Vec<u8> = [200, 100, 50].repeat(64);
+    let palette = load(&pal);
+    // red dims green and blue
+    assert_eq!(palette.rgb(0x1 << 6), (200, 81, 40));
+    // red and green both dim blue
+    assert_eq!(palette.rgb(0x3 << 6), (163, 81, 33));
+    // all three darken every channel twice
+    assert_eq!(palette.rgb(0x7 << 6), (133, 66, 33));
+    // the blacks are left alone
+    assert_eq!(palette.rgb(0x7 << 6 | 0x0E), (200, 100, 50));
+}
@@ -0,0 +1,64 @@
+// Checks the PPU address decoder: nametable mirroring as picked by the
+// cartridge header, the $3000 and 14-bit mirrors, and palette RAM aliasing.
+
+mod common;
+
+use common::bus_with_flags;
+use nes::Bus;
+
+// Writes a distinct value to each logical nametable, then reports which
+// table each one reads back from
+fn layout(bus: &mut Bus) -> [u8; 4] {
+    let mut seen = [0; 4];
+    for table in 0..4 {
+        bus.ppu_write_16(0x2000 + table * 0x400 + 0x123, table as u8 + 1);
+    }
+    for table in 0..4 {
+        seen[table as usize] = bus.ppu_read_16(0x2000 + table * 0x400 + 0x123) - 1;
+    }
+    seen
+}
+
+#[test]
+fn nametable_mirroring() {
+    assert_eq!(
+        layout(&mut bus_with_flags(0b0000)),
+        [1, 1, 3, 3],
+        "horizontal"
+    );
+    assert_eq!(
+        layout(&mut bus_with_flags(0b0001)),
+        [2, 3, 2, 3],
+        "vertical"
+    );
+    assert_eq!(
+        layout(&mut bus_with_flags(0b1000)),
+        [0, 1, 2, 3],
+        "four-screen"
+    );
+}
+
+#[test]
+fn address_mirrors() {
+    let mut bus = bus_with_flags(0b0001);
+    bus.ppu_write_16(0x2345, 0xAB);
+    assert_eq!(bus.ppu_read_16(0x3345), 0xAB);
+    assert_eq!(bus.ppu_read_16(0x6345), 0xAB);
+    bus.ppu_write_16(0x3F01, 0x21);
+    assert_eq!(bus.ppu_read_16(0x3F21), 0x21);
+    assert_eq!(bus.ppu_read_16(0x3FE1), 0x21);
+}
+
+#[test]
+fn palette_mirroring() {
+    let mut bus = bus_with_flags(0);
+    for entry in [0x00, 0x04, 0x08, 0x0C] {
+        bus.ppu_write_16(0x3F10 + entry, 0x30 + entry as u8);
+        assert_eq!(bus.ppu_read_16(0x3F00 + entry), 0x30 + entry as u8);
+    }
+    // the other sprite entries are their own bytes
+    bus.ppu_write_16(0x3F01, 0x01);
+    bus.ppu_write_16(0x3F11, 0x11);
+    assert_eq!(bus.ppu_read_16(0x3F01), 0x01);
+    assert_eq!(bus.ppu_read_16(0x3F11), 0x11);
+}
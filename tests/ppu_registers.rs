@@ -0,0 +1,295 @@
+// Drives the PPU registers through the CPU side of the bus, the way a game
+// would, and checks their read side effects.
+
+mod common;
+
+use common::emulator_with_program;
+use nes::{Bus, Ppu};
+
+fn write(bus: &mut Bus, ppu: &mut Ppu, addr: u16, val: u8) {
+    bus.cpu_write_16_ppu_regs(addr, val, ppu);
+}
+
+fn read(bus: &mut Bus, ppu: &mut Ppu, addr: u16) -> u8 {
+    bus.cpu_read_16_ppu_regs(addr, ppu)
+}
+
+fn set_addr(bus: &mut Bus, ppu: &mut Ppu, addr: u16) {
+    write(bus, ppu, 0x2006, (addr >> 8) as u8);
+    write(bus, ppu, 0x2006, addr as u8);
+}
+
+#[test]
+fn data_reads_are_buffered() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    set_addr(&mut bus, &mut ppu, 0x2400);
+    for val in [0x11, 0x22, 0x33] {
+        write(&mut bus, &mut ppu, 0x2007, val);
+    }
+
+    set_addr(&mut bus, &mut ppu, 0x2400);
+    read(&mut bus, &mut ppu, 0x2007);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x11);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x22);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x33);
+    assert_eq!(ppu.v, 0x2404);
+
+    // +32 per access with PPUCTRL bit 2
+    write(&mut bus, &mut ppu, 0x2000, 0x04);
+    set_addr(&mut bus, &mut ppu, 0x2400);
+    read(&mut bus, &mut ppu, 0x2007);
+    assert_eq!(ppu.v, 0x2420);
+}
+
+#[test]
+fn palette_reads_skip_the_buffer() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    bus.ppu_write_16(0x2F05, 0x5A);
+    bus.ppu_write_16(0x3F05, 0x16);
+
+    set_addr(&mut bus, &mut ppu, 0x3F05);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x16);
+    // the buffer picked up the nametable byte under the palette
+    set_addr(&mut bus, &mut ppu, 0x0000);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x5A);
+}
+
+#[test]
+fn status_read_clears_vblank_and_toggle() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    ppu.status.vblank = true;
+    // a stray first write to $2006 leaves the toggle set
+    write(&mut bus, &mut ppu, 0x2006, 0x1D);
+    assert!(ppu.w);
+
+    assert_eq!(read(&mut bus, &mut ppu, 0x2002), 0x80 | 0x1D);
+    assert!(!ppu.status.vblank);
+    assert!(!ppu.w);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2002), 0x1D);
+}
+
+#[test]
+fn write_only_registers_read_the_io_latch() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    write(&mut bus, &mut ppu, 0x2003, 0x5A);
+    for addr in [0x2000, 0x2001, 0x2003, 0x2005, 0x2006, 0x3FF8] {
+        assert_eq!(read(&mut bus, &mut ppu, addr), 0x5A);
+    }
+
+    // $2002 fills its low 5 bits from the latch, and leaves all 8 behind
+    ppu.status.vblank = true;
+    assert_eq!(read(&mut bus, &mut ppu, 0x2002), 0x9A);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2000), 0x9A);
+
+    // so do $2004 and $2007 reads
+    write(&mut bus, &mut ppu, 0x2003, 0x00);
+    write(&mut bus, &mut ppu, 0x2004, 0x37);
+    write(&mut bus, &mut ppu, 0x2003, 0x00);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2004), 0x37);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2005), 0x37);
+    bus.ppu_write_16(0x3F00, 0x21);
+    set_addr(&mut bus, &mut ppu, 0x3F00);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2007), 0x21);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2006), 0x21);
+}
+
+#[test]
+fn vblank_starts_at_line_241() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    write(&mut bus, &mut ppu, 0x2000, 0x80);
+    while (ppu.line, ppu.cycle) != (241, 1) {
+        ppu.tick(&mut bus);
+    }
+    assert!(!ppu.status.vblank);
+    ppu.tick(&mut bus);
+    assert!(ppu.status.vblank);
+    assert!(bus.nmi);
+    // reading it clears the flag and releases /NMI straight away
+    assert_eq!(read(&mut bus, &mut ppu, 0x2002) & 0x80, 0x80);
+    assert!(!ppu.status.vblank);
+    assert!(!bus.nmi);
+
+    // a read the dot before sees it clear, and it isn't set this frame
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    while (ppu.line, ppu.cycle) != (241, 1) {
+        ppu.tick(&mut bus);
+    }
+    assert_eq!(read(&mut bus, &mut ppu, 0x2002) & 0x80, 0);
+    ppu.tick(&mut bus);
+    assert!(!ppu.status.vblank);
+    // but it is the next frame
+    ppu.tick(&mut bus);
+    while (ppu.line, ppu.cycle) != (241, 2) {
+        ppu.tick(&mut bus);
+    }
+    assert!(ppu.status.vblank);
+}
+
+// Runs LDA $2002 with NMI on so the read lands with the PPU about to run
+// `dot` of line 241, then the rest of vblank. Returns what was read and
+// whether the NMI handler ran.
+fn read_status_near_vblank(dot: u16) -> (u8, bool) {
+    // LDA #$80; STA $2000; LDA $2002; JMP *, and INC $00; RTI at $8100
+    let mut program = vec![0; 0x3FFC];
+    program[..11].copy_from_slice(&[
+        0xA9, 0x80, 0x8D, 0x00, 0x20, 0xAD, 0x02, 0x20, 0x4C, 0x08, 0x80,
+    ]);
+    program[0x100..0x103].copy_from_slice(&[0xE6, 0x00, 0x40]);
+    program[0x3FFA..].copy_from_slice(&[0x00, 0x81]);
+    let mut emulator = emulator_with_program(&program);
+    // the reset sequence, LDA and STA
+    for _ in 0..3 {
+        emulator.step_instruction();
+    }
+
+    // the read is the 4th cpu cycle, 12 dots in
+    emulator.ppu.line = 240;
+    emulator.ppu.cycle = 341 + dot - 12;
+    emulator.step_instruction();
+    let status = emulator.cpu.a;
+    while emulator.ppu.line != 250 {
+        emulator.clock();
+    }
+    (status, emulator.bus.ram[0] == 1)
+}
+
+#[test]
+fn status_read_races_vblank() {
+    // a cpu cycle early: nothing to see, and the NMI comes as usual
+    assert_eq!(read_status_near_vblank(0).0 & 0x80, 0);
+    assert!(read_status_near_vblank(0).1);
+    // the dot before: seen clear, and no flag or NMI this frame
+    assert_eq!(read_status_near_vblank(1), (0, false));
+    // in the same cpu cycle as the set: seen set, but the NMI is cancelled
+    for dot in 2..=4 {
+        assert_eq!(read_status_near_vblank(dot), (0x80, false), "dot {}", dot);
+    }
+    // a cpu cycle later the NMI has already been taken
+    assert_eq!(read_status_near_vblank(5), (0x80, true));
+}
+
+#[test]
+fn oam_data_and_address() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    write(&mut bus, &mut ppu, 0x2003, 0xFE);
+    for val in [0x10, 0x11, 0xFF] {
+        write(&mut bus, &mut ppu, 0x2004, val);
+    }
+    assert_eq!(ppu.oam_addr, 0x01);
+
+    // reads don't move OAMADDR
+    write(&mut bus, &mut ppu, 0x2003, 0xFF);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2004), 0x11);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2004), 0x11);
+    // attribute bytes have no bits 2-4
+    write(&mut bus, &mut ppu, 0x2003, 0x02);
+    write(&mut bus, &mut ppu, 0x2004, 0xFF);
+    write(&mut bus, &mut ppu, 0x2003, 0x02);
+    assert_eq!(read(&mut bus, &mut ppu, 0x2004), 0xE3);
+}
+
+#[test]
+fn dma_starts_at_oam_address() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    for n in 0..=0xFF {
+        bus.cpu_write_16(0x0300 + n, n as u8);
+    }
+    write(&mut bus, &mut ppu, 0x2003, 0x80);
+    write(&mut bus, &mut ppu, 0x4014, 0x03);
+    assert_eq!(ppu.oam_addr, 0x80);
+
+    // the whole page went in, wrapping past the end of OAM
+    for n in 0..=0xFFu8 {
+        write(&mut bus, &mut ppu, 0x2003, 0x80u8.wrapping_add(n));
+        let expected = if n & 0x03 == 2 { n & 0xE3 } else { n };
+        assert_eq!(read(&mut bus, &mut ppu, 0x2004), expected);
+    }
+}
+
+#[test]
+fn dma_stalls_the_cpu() {
+    // LDA #$02; STA $4014; JMP $8005, run from the reset vector at $8000
+    let mut emulator = emulator_with_program(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0x4C, 0x05, 0x80]);
+    emulator.step_instruction();
+    assert_eq!(emulator.step_instruction(), 2);
+    // 4 cycles for the store, then 513 for the copy plus one as it ends on
+    // an odd cycle
+    assert_eq!(emulator.cpu.cycles % 2, 1);
+    assert_eq!(emulator.step_instruction(), 4 + 514);
+    assert_eq!(emulator.step_instruction(), 3);
+}
+
+#[test]
+fn scroll_and_addr_fill_t() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    // nametable 3 into bits 10-11
+    write(&mut bus, &mut ppu, 0x2000, 0x03);
+    assert_eq!(ppu.t, 0x0C00);
+    // coarse X 15, fine X 5
+    write(&mut bus, &mut ppu, 0x2005, 0x7D);
+    assert_eq!((ppu.t, ppu.x, ppu.w), (0x0C0F, 5, true));
+    // coarse Y 11 into bits 5-9, fine Y 6 into bits 12-14
+    write(&mut bus, &mut ppu, 0x2005, 0x5E);
+    assert_eq!((ppu.t, ppu.w), (0x6D6F, false));
+
+    // the first $2006 write clears bit 14, the second copies t to v
+    write(&mut bus, &mut ppu, 0x2006, 0xFF);
+    assert_eq!((ppu.t, ppu.v, ppu.w), (0x3F6F, 0, true));
+    write(&mut bus, &mut ppu, 0x2006, 0x21);
+    assert_eq!((ppu.t, ppu.v, ppu.w), (0x3F21, 0x3F21, false));
+    assert_eq!(ppu.x, 5);
+}
+
+#[test]
+fn scroll_and_addr_share_the_toggle() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    write(&mut bus, &mut ppu, 0x2005, 0x00);
+    // taken as the second write: the low byte, and t goes to v
+    write(&mut bus, &mut ppu, 0x2006, 0x12);
+    assert_eq!((ppu.v, ppu.w), (0x0012, false));
+
+    write(&mut bus, &mut ppu, 0x2006, 0x07);
+    // taken as the second write: fine Y 7, coarse Y 31, the nametable bits
+    // from the $2006 write stay
+    write(&mut bus, &mut ppu, 0x2005, 0xFF);
+    assert_eq!((ppu.t, ppu.w), (0x77F2, false));
+    assert_eq!(ppu.v, 0x0012);
+}
+
+// Run the single dot at `line`, `cycle` with v set to `v`
+fn tick_at(bus: &mut Bus, ppu: &mut Ppu, line: u16, cycle: u16, v: u16) -> u16 {
+    ppu.line = line;
+    ppu.cycle = cycle;
+    ppu.v = v;
+    ppu.tick(bus);
+    ppu.v
+}
+
+#[test]
+fn rendering_increments_v() {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    // background on
+    write(&mut bus, &mut ppu, 0x2001, 0x08);
+
+    // coarse X moves on after each tile, and into the next nametable after 31
+    assert_eq!(tick_at(&mut bus, &mut ppu, 0, 8, 0x0005), 0x0006);
+    assert_eq!(tick_at(&mut bus, &mut ppu, 0, 8, 0x001F), 0x0400);
+    assert_eq!(tick_at(&mut bus, &mut ppu, 0, 8, 0x041F), 0x0000);
+
+    // dot 256 moves fine Y on, which carries into coarse Y (bits 0-4 are
+    // coarse X, bumped on the same dot)
+    let y = |v: u16| v & 0x7BE0;
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x3000)), 0x4000);
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x70A0)), 0x00C0);
+    // row 29 is the last of tiles and wraps into the next nametable
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x73A0)), 0x0800);
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x7BA0)), 0x0000);
+    // rows 30 and 31 are attributes, and 31 wraps in place
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x73C0)), 0x03E0);
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x73E0)), 0x0000);
+    assert_eq!(y(tick_at(&mut bus, &mut ppu, 0, 256, 0x7BE0)), 0x0800);
+
+    // nothing moves with rendering off
+    write(&mut bus, &mut ppu, 0x2001, 0x00);
+    assert_eq!(tick_at(&mut bus, &mut ppu, 0, 256, 0x73A0), 0x73A0);
+}
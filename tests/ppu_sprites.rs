@@ -0,0 +1,228 @@
+// Sprite evaluation, fetching and sprite 0 hit, and the colors that come out
+// of the pixel pipeline, with OAM loaded through $2003/$2004 and the PPU
+// ticked dot by dot.
+
+use nes::{Bus, Ppu};
+
+const BACKDROP: u16 = 0x0F;
+const BACKGROUND: u16 = 0x21;
+const SPRITE_1: u16 = 0x16;
+const SPRITE_2: u16 = 0x2A;
+
+fn write(bus: &mut Bus, ppu: &mut Ppu, addr: u16, val: u8) {
+    bus.cpu_write_16_ppu_regs(addr, val, ppu);
+}
+
+// A PPU with `sprites` (4 bytes each) at the start of OAM and the rest of
+// OAM off screen. Background palette color 1 and sprite palette colors 1
+// and 2 are set, tile 0 of the $1000 table is solid color 1 for an opaque
+// background, and tile 1 of the $0000 table is solid for sprites.
+fn setup(sprites: &[u8], control: u8, mask: u8) -> (Bus, Ppu) {
+    let (mut bus, mut ppu) = (Bus::new(), Ppu::new());
+    bus.ppu_write_16(0x3F00, BACKDROP as u8);
+    bus.ppu_write_16(0x3F01, BACKGROUND as u8);
+    bus.ppu_write_16(0x3F11, SPRITE_1 as u8);
+    bus.ppu_write_16(0x3F12, SPRITE_2 as u8);
+    for row in 0..8 {
+        bus.ppu_write_16(0x1000 + row, 0xFF);
+        bus.ppu_write_16(0x0010 + row, 0xFF);
+    }
+
+    write(&mut bus, &mut ppu, 0x2003, 0x00);
+    for n in 0..=0xFF {
+        let val = sprites.get(n).copied().unwrap_or(0xF0);
+        write(&mut bus, &mut ppu, 0x2004, val);
+    }
+    write(&mut bus, &mut ppu, 0x2000, control);
+    write(&mut bus, &mut ppu, 0x2001, mask);
+    (bus, ppu)
+}
+
+// Tick until the PPU is about to run the dot at `line`, `cycle`
+fn run_to(bus: &mut Bus, ppu: &mut Ppu, line: u16, cycle: u16) {
+    while ppu.line != line || ppu.cycle != cycle {
+        ppu.tick(bus);
+    }
+}
+
+fn pixel(ppu: &Ppu, x: usize, y: usize) -> u16 {
+    ppu.framebuffer[y * 256 + x]
+}
+
+// Sprites at OAM y 9 (drawn on lines 10-17) every 10 pixels, with tile 1
+fn row_of_sprites(count: u8) -> Vec<u8> {
+    (0..count).flat_map(|n| [9, 1, 0, n * 10]).collect()
+}
+
+#[test]
+fn eight_sprites_per_line() {
+    let (mut bus, mut ppu) = setup(&row_of_sprites(8), 0x00, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 70, 9), BACKDROP);
+    assert_eq!(pixel(&ppu, 0, 10), SPRITE_1);
+    assert_eq!(pixel(&ppu, 77, 17), SPRITE_1);
+    assert_eq!(pixel(&ppu, 70, 18), BACKDROP);
+    assert!(!ppu.status.overflow);
+
+    // a 9th on the same line isn't drawn, and flags the overflow
+    let (mut bus, mut ppu) = setup(&row_of_sprites(9), 0x00, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 70, 10), SPRITE_1);
+    assert_eq!(pixel(&ppu, 80, 10), BACKDROP);
+    assert!(ppu.status.overflow);
+}
+
+#[test]
+fn overflow_check_walks_diagonally() {
+    // Past 8 sprites the PPU reads sprite 8's y, then sprite 9's tile, then
+    // sprite 10's attribute... as if they were y positions
+    let mut sprites = row_of_sprites(8);
+    sprites.extend([0xF0, 0xF0, 0xF0, 0xF0]);
+    // a tile number that's in range for lines 9-16 flags an overflow
+    sprites.extend([0xF0, 12, 0xF0, 0xF0]);
+    let (mut bus, mut ppu) = setup(&sprites, 0x00, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert!(ppu.status.overflow);
+
+    // while a real 9th sprite gets missed, its tile is compared instead
+    let mut sprites = row_of_sprites(8);
+    sprites.extend([0xF0, 0xF0, 0xF0, 0xF0]);
+    sprites.extend([9, 0xF0, 0xF0, 0xF0]);
+    let (mut bus, mut ppu) = setup(&sprites, 0x00, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert!(!ppu.status.overflow);
+}
+
+#[test]
+fn tall_sprites() {
+    // Tile byte $03: the $1000 table (bit 0), tiles 2 and 3. Tile 2's first
+    // row is solid and the rest only have the 2 left pixels, tile 3 has the 2
+    // right pixels in color 2
+    let sprites = [9, 0x03, 0x00, 0, 9, 0x03, 0xC0, 100];
+    let (mut bus, mut ppu) = setup(&sprites, 0x20, 0x1E);
+    bus.ppu_write_16(0x1020, 0xFF);
+    for row in 1..8 {
+        bus.ppu_write_16(0x1020 + row, 0xC0);
+    }
+    for row in 0..8 {
+        bus.ppu_write_16(0x1038 + row, 0x03);
+    }
+    run_to(&mut bus, &mut ppu, 30, 0);
+
+    // top half from tile 2, bottom half from tile 3
+    assert_eq!(pixel(&ppu, 7, 10), SPRITE_1);
+    assert_eq!(pixel(&ppu, 1, 11), SPRITE_1);
+    assert_eq!(pixel(&ppu, 2, 11), BACKDROP);
+    assert_eq!(pixel(&ppu, 1, 18), BACKDROP);
+    assert_eq!(pixel(&ppu, 7, 25), SPRITE_2);
+    assert_eq!(pixel(&ppu, 0, 26), BACKDROP);
+
+    // flipped both ways: tile 3 on top, tile 2 at the bottom, mirrored
+    assert_eq!(pixel(&ppu, 100, 10), SPRITE_2);
+    assert_eq!(pixel(&ppu, 102, 10), BACKDROP);
+    assert_eq!(pixel(&ppu, 107, 18), SPRITE_1);
+    assert_eq!(pixel(&ppu, 105, 18), BACKDROP);
+    // tile 2's solid first row is the last one drawn
+    assert_eq!(pixel(&ppu, 100, 25), SPRITE_1);
+}
+
+#[test]
+fn sprite_zero_hit_after_clipping() {
+    // Opaque background from the $1000 table everywhere, sprite 0 at x=0
+    let sprites = [9, 1, 0, 0];
+    let (mut bus, mut ppu) = setup(&sprites, 0x10, 0x1E);
+    run_to(&mut bus, &mut ppu, 10, 1);
+    assert!(!ppu.status.hit);
+    ppu.tick(&mut bus);
+    assert!(ppu.status.hit);
+
+    // with the left 8 pixels clipped there's nothing left to hit
+    let (mut bus, mut ppu) = setup(&sprites, 0x10, 0x18);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert!(!ppu.status.hit);
+
+    // one pixel further right and it hits at x=8
+    let sprites = [9, 1, 0, 1];
+    let (mut bus, mut ppu) = setup(&sprites, 0x10, 0x18);
+    run_to(&mut bus, &mut ppu, 10, 9);
+    assert!(!ppu.status.hit);
+    ppu.tick(&mut bus);
+    assert!(ppu.status.hit);
+}
+
+#[test]
+fn sprite_zero_hit_misses_x_255() {
+    let (mut bus, mut ppu) = setup(&[9, 1, 0, 255], 0x10, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 255, 10), SPRITE_1);
+    assert!(!ppu.status.hit);
+
+    let (mut bus, mut ppu) = setup(&[9, 1, 0, 254], 0x10, 0x1E);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert!(ppu.status.hit);
+}
+
+#[test]
+fn pre_render_line_clears_flags() {
+    let mut sprites = row_of_sprites(9);
+    sprites[..4].copy_from_slice(&[9, 1, 0, 100]);
+    let (mut bus, mut ppu) = setup(&sprites, 0x10, 0x1E);
+    run_to(&mut bus, &mut ppu, 261, 1);
+    assert!(ppu.status.hit);
+    assert!(ppu.status.overflow);
+    assert!(ppu.status.vblank);
+
+    ppu.tick(&mut bus);
+    assert!(!ppu.status.hit);
+    assert!(!ppu.status.overflow);
+    assert!(!ppu.status.vblank);
+}
+
+#[test]
+fn greyscale_and_emphasis() {
+    // greyscale keeps only the brightness column, for every layer
+    let (mut bus, mut ppu) = setup(&[9, 1, 0, 0], 0x10, 0x1F);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 100, 10), BACKGROUND & 0x30);
+    assert_eq!(pixel(&ppu, 0, 10), SPRITE_1 & 0x30);
+    let (mut bus, mut ppu) = setup(&[], 0x00, 0x01);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 100, 10), BACKDROP & 0x30);
+
+    // red and blue emphasis (PPUMASK bits 5 and 7) land in bits 6 and 8
+    let (mut bus, mut ppu) = setup(&[9, 1, 0, 0], 0x10, 0xBE);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 100, 10), BACKGROUND | 0b101 << 6);
+    assert_eq!(pixel(&ppu, 0, 10), SPRITE_1 | 0b101 << 6);
+    // green alone
+    let (mut bus, mut ppu) = setup(&[], 0x00, 0x40);
+    run_to(&mut bus, &mut ppu, 20, 0);
+    assert_eq!(pixel(&ppu, 100, 10), BACKDROP | 0b010 << 6);
+}
+
+#[test]
+fn sprite_priority() {
+    // Sprite 0 behind the background at x=0, sprite 1 behind at x=80, and at
+    // x=40 a behind sprite 2 ahead in OAM of a front sprite 3 in palette 1
+    let sprites = [
+        9, 1, 0x20, 0, 9, 1, 0x20, 80, 9, 1, 0x20, 40, 9, 1, 0x01, 40,
+    ];
+    let (mut bus, mut ppu) = setup(&sprites, 0x10, 0x1E);
+    bus.ppu_write_16(0x3F15, 0x1A);
+    // tile row 1 is transparent background (blank tile 1) over x=80-103,
+    // and opaque elsewhere
+    for column in 10..13 {
+        bus.ppu_write_16(0x2020 + column, 1);
+    }
+    run_to(&mut bus, &mut ppu, 20, 0);
+
+    // behind sprites show only where the background is transparent
+    assert_eq!(pixel(&ppu, 0, 10), BACKGROUND);
+    assert_eq!(pixel(&ppu, 80, 10), SPRITE_1);
+    // the first opaque sprite in OAM order decides, so the behind sprite
+    // hides the front one under it
+    assert_eq!(pixel(&ppu, 40, 10), BACKGROUND);
+    // with neither opaque, the backdrop at $3F00 shows
+    assert_eq!(pixel(&ppu, 95, 10), BACKDROP);
+    assert_eq!(pixel(&ppu, 95, 16), BACKGROUND);
+}
@@ -0,0 +1,244 @@
+// Tom Harte's SingleStepTests for the NES 2A03 (the nes6502 set from
+// https://github.com/SingleStepTests/65x02). Each opcode file holds
+// thousands of vectors with the starting state, the state after one
+// instruction and every bus access in between. The files are far too big to
+// check in, so the test is ignored by default: point NES_SINGLE_STEP_TESTS at
+// the directory holding 00.json..ff.json and run it with --ignored.
+
+use nes::cpu::Addressing::*;
+use nes::cpu::Instructions::*;
+use nes::cpu::{Cpu, CpuBus, Instructions};
+use nes::opcodes::OPCODES;
+use serde_json::Value;
+use std::path::PathBuf;
+
+const DEFAULT_DIR: &str = "tests/data/nes6502/v1";
+// Failures listed per opcode before moving on to the next file
+const MAX_REPORTED: usize = 3;
+
+#[derive(PartialEq, Debug)]
+struct Access {
+    addr: u16,
+    val: u8,
+    write: bool,
+}
+
+// Flat 64K of RAM: no mirroring, no PPU registers, no mapper
+struct TestBus {
+    ram: Vec<u8>,
+    accesses: Vec<Access>,
+}
+
+impl CpuBus for TestBus {
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let val = self.ram[addr as usize];
+        self.accesses.push(Access {
+            addr,
+            val,
+            write: false,
+        });
+        val
+    }
+
+    fn cpu_write(&mut self, addr: u16, val: u8) {
+        self.ram[addr as usize] = val;
+        self.accesses.push(Access {
+            addr,
+            val,
+            write: true,
+        });
+    }
+}
+
+fn field(state: &Value, name: &str) -> u64 {
+    state[name].as_u64().unwrap()
+}
+
+fn load_state(cpu: &mut Cpu, bus: &mut TestBus, state: &Value) {
+    cpu.pc = field(state, "pc") as u16;
+    cpu.stack_pointer = field(state, "s") as u8;
+    cpu.a = field(state, "a") as u8;
+    cpu.x = field(state, "x") as u8;
+    cpu.y = field(state, "y") as u8;
+    cpu.flags_from_byte(field(state, "p") as u8);
+    for entry in state["ram"].as_array().unwrap() {
+        bus.ram[entry[0].as_u64().unwrap() as usize] = entry[1].as_u64().unwrap() as u8;
+    }
+}
+
+fn check_state(cpu: &mut Cpu, bus: &TestBus, state: &Value) -> Result<(), String> {
+    let registers = [
+        ("pc", cpu.pc as u64, field(state, "pc")),
+        ("s", cpu.stack_pointer as u64, field(state, "s")),
+        ("a", cpu.a as u64, field(state, "a")),
+        ("x", cpu.x as u64, field(state, "x")),
+        ("y", cpu.y as u64, field(state, "y")),
+        // bits 4 and 5 aren't stored in the cpu, they only exist when pushed
+        (
+            "p",
+            (cpu.flags_to_byte() & 0xCF) as u64,
+            field(state, "p") & 0xCF,
+        ),
+    ];
+    for (name, mine, expected) in registers {
+        if mine != expected {
+            return Err(format!(
+                "{} is {:02x}, expected {:02x}",
+                name, mine, expected
+            ));
+        }
+    }
+    for entry in state["ram"].as_array().unwrap() {
+        let addr = entry[0].as_u64().unwrap() as usize;
+        let expected = entry[1].as_u64().unwrap() as u8;
+        if bus.ram[addr] != expected {
+            return Err(format!(
+                "ram[{:04x}] is {:02x}, expected {:02x}",
+                addr, bus.ram[addr], expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_accesses(bus: &TestBus, cycles: &Value) -> Result<(), String> {
+    let expected: Vec<Access> = cycles
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|cycle| Access {
+            addr: cycle[0].as_u64().unwrap() as u16,
+            val: cycle[1].as_u64().unwrap() as u8,
+            write: cycle[2].as_str().unwrap() == "write",
+        })
+        .collect();
+    if bus.accesses.len() != expected.len() {
+        return Err(format!(
+            "took {} cycles, expected {}",
+            bus.accesses.len(),
+            expected.len()
+        ));
+    }
+    for (n, (mine, expected)) in bus.accesses.iter().zip(expected.iter()).enumerate() {
+        if mine != expected {
+            return Err(format!("cycle {}: {:?}, expected {:?}", n, mine, expected));
+        }
+    }
+    Ok(())
+}
+
+fn run_vector(test: &Value) -> Result<(), String> {
+    let mut cpu = Cpu::new();
+    let mut bus = TestBus {
+        ram: vec![0; 0x10000],
+        accesses: vec![],
+    };
+    load_state(&mut cpu, &mut bus, &test["initial"]);
+    cpu.step_instruction(&mut bus);
+    check_state(&mut cpu, &bus, &test["final"])?;
+    check_accesses(&bus, &test["cycles"])
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests vectors, see NES_SINGLE_STEP_TESTS"]
+fn single_step_tests() {
+    let dir =
+        PathBuf::from(std::env::var("NES_SINGLE_STEP_TESTS").unwrap_or(DEFAULT_DIR.to_string()));
+    assert!(
+        dir.is_dir(),
+        "no SingleStepTests vectors in {}",
+        dir.display()
+    );
+
+    let mut failures = vec![];
+    for (opcode, entry) in OPCODES.iter().enumerate() {
+        // KIL locks the bus up, there is no end of instruction to compare at
+        if entry.instr == Instructions::KIL {
+            continue;
+        }
+        let path = dir.join(format!("{:02x}.json", opcode));
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(why) => {
+                failures.push(format!("{}: {}", path.display(), why));
+                continue;
+            }
+        };
+        let tests: Value = serde_json::from_str(&json).unwrap();
+        let mut reported = 0;
+        for test in tests.as_array().unwrap() {
+            if let Err(why) = run_vector(test) {
+                failures.push(format!(
+                    "{:02x} \"{}\": {}",
+                    opcode,
+                    test["name"].as_str().unwrap(),
+                    why
+                ));
+                reported += 1;
+                if reported == MAX_REPORTED {
+                    break;
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} failing vectors:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+// Cycles one instruction at $0200 takes with the given operand byte, flags and
+// index registers, on otherwise empty memory
+fn cycles_taken(opcode: u8, operand: u8, flags: u8, index: u8) -> u16 {
+    let mut cpu = Cpu::new();
+    let mut bus = TestBus {
+        ram: vec![0; 0x10000],
+        accesses: vec![],
+    };
+    bus.ram[0x0200] = opcode;
+    bus.ram[0x0201] = operand;
+    // so ($operand),Y points at $00operand too
+    bus.ram[operand as usize] = operand;
+    cpu.pc = 0x0200;
+    cpu.x = index;
+    cpu.y = index;
+    cpu.flags_from_byte(flags);
+    cpu.step_instruction(&mut bus)
+}
+
+// OPCODES' timing columns against the cpu: the base count when nothing
+// crosses a page and no branch is taken, one more when an indexed read does
+// cross
+#[test]
+fn opcode_table_cycles() {
+    for opcode in 0..=0xFF {
+        let entry = &OPCODES[opcode as usize];
+        if entry.instr == KIL {
+            continue;
+        }
+        // flags that leave every branch untaken
+        let flags = match entry.instr {
+            BPL | BVC | BCC | BNE => 0xFF,
+            _ => 0x00,
+        };
+        assert_eq!(
+            cycles_taken(opcode, 0x00, flags, 0),
+            entry.cycles as u16,
+            "opcode {:02X}",
+            opcode
+        );
+        if matches!(entry.addr, ABX | ABY | IDY) {
+            // $00FF + 1
+            assert_eq!(
+                cycles_taken(opcode, 0xFF, flags, 1),
+                (entry.cycles + entry.page_cross as u8) as u16,
+                "opcode {:02X} crossing a page",
+                opcode
+            );
+        }
+    }
+}
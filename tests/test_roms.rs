@@ -0,0 +1,55 @@
+// Every .nes under NES_TEST_ROMS (default tests/roms) that speaks the $6000
+// status protocol: blargg's cpu/ppu/apu/mapper suites and friends. The ROMs
+// aren't ours to redistribute, so the test is ignored by default: run it with
+// --ignored once they're in place.
+
+use nes::test_rom::run_test_rom;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DIR: &str = "tests/roms";
+const MAX_SECONDS: u64 = 60;
+
+fn find_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_roms(&path, roms);
+        } else if path.extension().is_some_and(|ext| ext == "nes") {
+            roms.push(path);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs test roms, see NES_TEST_ROMS"]
+fn test_roms() {
+    let dir = PathBuf::from(std::env::var("NES_TEST_ROMS").unwrap_or(DEFAULT_DIR.to_string()));
+    assert!(dir.is_dir(), "no test roms in {}", dir.display());
+    let mut roms = vec![];
+    find_roms(&dir, &mut roms);
+    roms.sort();
+    assert!(!roms.is_empty(), "no test roms in {}", dir.display());
+
+    let mut failures = vec![];
+    for rom in &roms {
+        match run_test_rom(rom.to_str().unwrap(), MAX_SECONDS) {
+            Ok(result) if result.passed() => println!("passed {}", rom.display()),
+            Ok(result) => failures.push(format!(
+                "{}: failed with code {}\n{}",
+                rom.display(),
+                result.code,
+                result.message
+            )),
+            Err(why) => failures.push(format!("{}: {}", rom.display(), why)),
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} test roms failed:\n{}",
+            failures.len(),
+            roms.len(),
+            failures.join("\n")
+        );
+    }
+}